@@ -1,8 +1,7 @@
-use ccxt::coinbase::Coinbase;
 use ccxt::kraken::Kraken;
-use ccxt::exchange::{ApiConfig, Balance, ServerTime, SystemStatus};
+use ccxt::exchange::{ApiConfig, Balance, Markets, ServerTime, SystemStatus};
+use ccxt::registry::{create_exchange, EXCHANGES};
 use std::fs::File;
-use std::io::Read;
 
 #[tokio::main]
 async fn main() {
@@ -11,9 +10,17 @@ async fn main() {
     let path = std::env::current_dir().unwrap();
     println!("The current directory is {}", path.display());
 
+    for id in EXCHANGES {
+        let exchange = create_exchange(id, ApiConfig::default()).unwrap();
+        println!("{} capabilities:", exchange.exchange().name);
+        for (method, capability) in exchange.has() {
+            println!("  {}: {:?}", method, capability);
+        }
+    }
+
     let h = tokio::spawn(async {
         println!("Coinbase get time");
-        let coinbase = Coinbase::new("coinbase");
+        let coinbase = create_exchange("coinbase", ApiConfig::default()).unwrap();
         let time = coinbase.get_time().await.unwrap();
         println!("Coinbase time: {}", time);
         });
@@ -33,6 +40,12 @@ async fn main() {
         let status = kraken.get_status().await.unwrap();
         println!("Kraken is: {}", status);
 
+        let markets = kraken.fetch_markets().await;
+        match markets {
+            Ok(m) => println!("Kraken markets: {}", m.len()),
+            Err(x) => println!("Kraken markets failed: {:?}", x)
+        };
+
         let b = kraken.get_balance().await;
         match b {
             Ok(a) => println!("Kraken balance is: {:?}", a),
@@ -46,11 +59,11 @@ async fn main() {
         }
     });
 
-    let _t1 = match h.await {
+    match h.await {
         Ok(_) => println!("coinbase completed"),
         Err(e) => println!("coinbase failed: {:?}", e)
     };
-    let _t2 = match h1.await {
+    match h1.await {
         Ok(_) => println!("kraken completed"),
         Err(e) => println!("kraken failed: {:?}", e)
     };
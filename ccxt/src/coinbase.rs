@@ -1,30 +1,229 @@
-use std::collections::HashMap;
+use std::{collections::{BTreeMap, HashMap}, sync::{Arc, RwLock}};
 
 use async_trait::async_trait;
-use crate::{ApiRequest, DateTime, Result, exchange::*, errors::Error};
+use serde::de::DeserializeOwned;
+use reqwest::StatusCode;
+use chrono::Utc;
+use data_encoding::HEXLOWER;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use crate::{ApiRequest, DateTime, Decimal, Result, rate_limiter::RateLimiter, retry::{self, RetryPolicy}, exchange::*, errors::{Error, ErrorTable}};
 
-#[derive(Debug)]
+/// Coinbase error ids.
+const ERRORS: ErrorTable = &[
+    ("authentication_error", Error::AuthenticationError),
+    ("invalid_token", Error::AuthenticationError),
+    ("revoked_token", Error::AuthenticationError),
+    ("expired_token", Error::AuthenticationError),
+    ("two_factor_required", Error::AuthenticationError),
+    ("invalid_scope", Error::PermissionDenied),
+    ("unverified_email", Error::PermissionDenied),
+    ("personal_details_required", Error::PermissionDenied),
+    ("identity_verification_required", Error::PermissionDenied),
+    ("rate_limit_exceeded", Error::RateLimitExceeded),
+    ("internal_server_error", Error::ExchangeNotAvailable),
+];
+
+/// HTTP status codes of responses without a known error id, matched as prefixes.
+pub(crate) const STATUS_ERRORS: ErrorTable = &[
+    ("401", Error::AuthenticationError),
+    ("403", Error::PermissionDenied),
+    ("429", Error::RateLimitExceeded),
+    ("5", Error::ExchangeNotAvailable),
+];
+
+/// Hex encoded HMAC-SHA256 of the CB-ACCESS-SIGN header, shared with the Advanced Trade API.
+pub fn sign(secret: &str, timestamp: &str, method: &str, request_path: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}{}{}{}", timestamp, method, request_path, body).as_bytes());
+    HEXLOWER.encode(&mac.finalize().into_bytes())
+}
+
+#[derive(Debug, Clone)]
 pub struct Coinbase {
     pub exchange: Exchange,
     pub api: Api,
-    pub http_client: reqwest::Client
+    pub http_client: reqwest::Client,
+    pub rate_limiter: RateLimiter,
+    pub retry_policy: RetryPolicy,
+    markets: Arc<RwLock<MarketCache>>,
 }
 
 impl Coinbase {
-    pub fn new(id: &'static str) -> Self {
-        Coinbase {
-            exchange: Exchange::new(id, "Coinbase")
+    pub fn new(id: &'static str, api_key: String, api_secret: String) -> Self {
+        let exchange = Exchange::new(id, "Coinbase")
             .rate_limit(400)
             .headers("CB-VERSION", "2018-05-30")
             .user_agent(UserAgent::Chrome)
-            .countries(Country::UnitedStates),
+            .countries(Country::UnitedStates);
+        Coinbase {
+            rate_limiter: RateLimiter::from_millis(exchange.rate_limit),
+            exchange,
             api: Api::new("https://api.coinbase.com", "v2")
-            .api_key("1234".to_string())
-            .api_secret("111".to_string())
+            .api_key(api_key)
+            .api_secret(api_secret)
+            .function(Functionality::Accounts,FunctionalityParams::new(AccessType::Private, Action::Get, "accounts"))
+            .function(Functionality::Assets,FunctionalityParams::new(AccessType::Public, Action::Get, "currencies"))
+            .function(Functionality::BuyPrice,FunctionalityParams::new(AccessType::Public, Action::Get, "prices/{currency_pair}/buy"))
+            .function(Functionality::CryptoAssets,FunctionalityParams::new(AccessType::Public, Action::Get, "currencies/crypto"))
+            .function(Functionality::SellPrice,FunctionalityParams::new(AccessType::Public, Action::Get, "prices/{currency_pair}/sell"))
+            .function(Functionality::SpotPrice,FunctionalityParams::new(AccessType::Public, Action::Get, "prices/{currency_pair}/spot"))
             .function(Functionality::Time,FunctionalityParams::new(AccessType::Public, Action::Get, "time")),
-            http_client: reqwest::Client::new()  
+            http_client: reqwest::Client::new(),
+            markets: Arc::new(RwLock::new(MarketCache::default())),
+            retry_policy: RetryPolicy::default(),
             }
         }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Translates an unified symbol (BTC/USD) into the Coinbase currency pair (BTC-USD).
+    pub fn market_id(&self, symbol: &str) -> Result<String> {
+        if let Some(m) = self.markets.read().unwrap().market(symbol) {
+            return Ok(m.id.clone());
+        }
+        let (base, quote) = split_symbol(symbol)?;
+        Ok(format!("{}-{}", self.exchange.currency_id(base), self.exchange.currency_id(quote)))
+    }
+
+    /// Translates a Coinbase currency pair (BTC-USD) into an unified symbol (BTC/USD).
+    pub fn symbol(&self, market_id: &str) -> String {
+        if let Some(m) = self.markets.read().unwrap().market_by_id(market_id) {
+            return m.symbol.clone();
+        }
+        match market_id.split_once('-') {
+            Some((base, quote)) => format!("{}/{}", self.exchange.common_currency_code(base), self.exchange.common_currency_code(quote)),
+            None => market_id.to_string()
+        }
+    }
+
+    pub async fn get_data_no_params<T>(&self, f: &Functionality) -> Result<T>
+        where T: DeserializeOwned
+    {
+        self.get_data::<T>(f, HashMap::<&str, String>::new()).await
+    }
+
+    pub async fn get_data<T>(&self, f: &Functionality, payload: HashMap<&str, String>) -> Result<T>
+    where T: DeserializeOwned
+    {
+        Ok(self.get_page::<T>(f, payload).await?.data)
+    }
+
+    /// Follows the `starting_after` cursor until every item is fetched.
+    pub async fn get_all_pages<T>(&self, f: &Functionality, payload: HashMap<&str, String>) -> Result<Vec<T>>
+    where T: DeserializeOwned
+    {
+        let mut items = Vec::new();
+        let mut payload = payload;
+        loop {
+            let page = self.get_page::<Vec<T>>(f, payload.clone()).await?;
+            items.extend(page.data);
+            match page.pagination.and_then(|p| p.next_starting_after) {
+                Some(cursor) => payload.insert("starting_after", cursor),
+                None => return Ok(items),
+            };
+        }
+    }
+
+    /// Calls retry transient failures unless they are marked non idempotent.
+    async fn get_page<T>(&self, f: &Functionality, payload: HashMap<&str, String>) -> Result<Data<T>>
+    where T: DeserializeOwned
+    {
+        if !self.api.get_function_params(f)?.idempotent {
+            return self.send_data(f, payload).await;
+        }
+        self.retry_policy.retry(retry::is_transient, || self.send_data(f, payload.clone())).await
+    }
+
+    async fn send_data<T>(&self, f: &Functionality, payload: HashMap<&str, String>) -> Result<Data<T>>
+    where T: DeserializeOwned
+    {
+        let fp = self.api.get_function_params(f)?;
+        self.rate_limiter.acquire(fp.cost).await;
+        let rb = self.get_request(f, payload)?;
+        let r = retry::error_for_transient_status(rb.send().await?)?;
+        let status = r.status();
+        if !status.is_success() {
+            let body = r.text().await?;
+            return Err(self.parse_error(fp.uri_path, status, &body));
+        }
+
+        Ok(r.json::<Data<T>>().await?)
+    }
+
+    /// Signature of timestamp, method, request path with the query and body.
+    pub fn get_signature(&self, timestamp: &str, method: &str, request_path: &str, body: &str) -> String {
+        sign(self.api.secret.as_deref().unwrap_or_default(), timestamp, method, request_path, body)
+    }
+
+    /// Maps a failed response onto an error variant by its error id, or by the HTTP status.
+    pub fn parse_error(&self, endpoint: &'static str, status: StatusCode, body: &str) -> Error {
+        let (id, message) = match serde_json::from_str::<ErrorResponse>(body) {
+            Ok(r) if !r.errors.is_empty() => (r.errors[0].id.clone(), r.errors[0].message.clone()),
+            _ => (String::new(), body.to_string()),
+        };
+        match Error::from_table(ERRORS, &id, endpoint, &message) {
+            Error::ApiCallError(_) => Error::from_table(STATUS_ERRORS, status.as_str(), endpoint, &message),
+            e => e,
+        }
+    }
+
+    /// Coinbase buys at the ask and sells at the bid.
+    pub fn parse_ticker(&self, symbol: &str, spot: &Price, buy: &Price, sell: &Price) -> Ticker {
+        Ticker {
+            symbol: symbol.to_string(),
+            timestamp: Some(Utc::now()),
+            bid: Some(sell.amount),
+            ask: Some(buy.amount),
+            last: Some(spot.amount),
+            ..Default::default()
+        }
+    }
+
+    /// Coinbase wallets do not report holds, the whole balance is free.
+    pub fn parse_balance(&self, accounts: &[Account]) -> AccountBalance {
+        let mut result = AccountBalance::default();
+        for a in accounts {
+            result.add(self.exchange.common_currency_code(&a.balance.currency), a.balance.amount, Decimal::ZERO);
+        }
+        result
+    }
+
+    /// The v2 API has no product listing, so every crypto currency is paired with every fiat currency.
+    /// The list is synthetic and contains pairs Coinbase does not trade, so `active` is unknown;
+    /// `coinbaseadvanced` lists the real products.
+    pub fn parse_markets(&self, fiat: &[Currency], crypto: &[CryptoCurrency]) -> Vec<Market> {
+        let mut markets = Vec::with_capacity(fiat.len() * crypto.len());
+        for base in crypto {
+            let base_code = self.exchange.common_currency_code(&base.code);
+            for quote in fiat {
+                let quote_code = self.exchange.common_currency_code(&quote.id);
+                markets.push(Market {
+                    id: format!("{}-{}", base.code, quote.id),
+                    symbol: format!("{}/{}", base_code, quote_code),
+                    base: base_code.clone(),
+                    quote: quote_code,
+                    base_id: base.code.clone(),
+                    quote_id: quote.id.clone(),
+                    active: None,
+                    precision: MarketPrecision {
+                        price: Some(quote.min_size.normalize().scale()),
+                        amount: Some(base.exponent),
+                        cost: None,
+                    },
+                    limits: MarketLimits {
+                        cost: MinMax { min: Some(quote.min_size), max: None },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+            }
+        }
+        markets
+    }
 }
 
 impl ApiCalls for Coinbase {
@@ -39,44 +238,248 @@ impl ApiCalls for Coinbase {
             params.uri_path)
     }
 
-    fn get_request(&self, f: &Functionality, payload: HashMap<&str, String>) -> Result<ApiRequest>
+    fn get_request(&self, f: &Functionality, mut payload: HashMap<&str, String>) -> Result<ApiRequest>
     {
         let fp = self.api.get_function_params(f)?;
+        let uri_path = Api::expand_uri_path(&self.get_uri_path(fp), &mut payload);
+        let method = match fp.action {
+            Action::Get => reqwest::Method::GET,
+            Action::Post => reqwest::Method::POST,
+            Action::Delete => reqwest::Method::DELETE,
+        };
+
+        // GET and DELETE send the payload as query string, POST as JSON body
+        let (request_path, body) = match fp.action {
+            Action::Post => (uri_path, serde_json::to_string(&payload)?),
+            _ if payload.is_empty() => (uri_path, String::new()),
+            _ => (format!("{}?{}", uri_path, Api::encode_uri(&payload)), String::new()),
+        };
+
+        let mut req = self.http_client.request(method.clone(), format!("{}{}", self.api.url, request_path));
+        for (name, value) in &self.exchange.headers {
+            req = req.header(*name, *value);
+        }
 
         match fp.access_type {
-            AccessType::Public => {
-                return Ok(
-                    self.http_client.get(self.get_url(fp))
-                        );
-            },
+            AccessType::Public => Ok(req),
             AccessType::Private => {
-                Err(Error::ApiFunctionNotSupported("Private secure functions"))
+                let key = self.api.key.as_deref()
+                    .filter(|k| !k.is_empty())
+                    .ok_or(Error::ArgumentsRequired("api key"))?;
+                let timestamp = Utc::now().timestamp().to_string();
+                let signature = self.get_signature(&timestamp, method.as_str(), &request_path, &body);
+                req = req.header("CB-ACCESS-KEY", key)
+                    .header("CB-ACCESS-SIGN", signature)
+                    .header("CB-ACCESS-TIMESTAMP", timestamp);
+                if fp.action == Action::Post {
+                    req = req.header("Content-Type", "application/json").body(body);
+                }
+                Ok(req)
             }
-        }        
+        }
     }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Time {
-    iso: DateTime,
-    epoch: u64,
+    pub iso: DateTime,
+    pub epoch: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Money {
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Price {
+    pub base: String,
+    pub currency: String,
+    pub amount: Decimal,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Account {
+    pub id: String,
+    pub name: String,
+    pub balance: Money,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Currency {
+    pub id: String,
+    pub name: String,
+    pub min_size: Decimal,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CryptoCurrency {
+    pub code: String,
+    pub name: String,
+    pub exponent: u32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ErrorEntry {
+    pub id: String,
+    pub message: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorResponse {
+    pub errors: Vec<ErrorEntry>,
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename(deserialize = "data"), rename_all = "camelCase")]
 pub struct Data<D> {
-    data: D
+    pub data: D,
+    pub pagination: Option<Pagination>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Pagination {
+    pub next_starting_after: Option<String>,
 }
 
 #[async_trait]
 impl ServerTime for Coinbase {
     async fn get_time(&self) -> Result<DateTime> {
-        
-        let rb = self.get_request(&Functionality::Time, HashMap::<&str, String>::new())?;
-        let r = rb.send().await?;
+        let time = self.get_data_no_params::<Time>(&Functionality::Time).await?;
+        Ok(time.iso)
+    }
+}
+
+#[async_trait]
+impl Balance for Coinbase {
+    async fn get_balance(&self) -> Result<AccountBalance> {
+        let mut payload = HashMap::new();
+        payload.insert("limit", "100".to_string());
+        let accounts = self.get_all_pages::<Account>(&Functionality::Accounts, payload).await?;
+        Ok(self.parse_balance(&accounts))
+    }
+}
+
+#[async_trait]
+impl Tickers for Coinbase {
+    async fn fetch_ticker(&self, symbol: &str) -> Result<Ticker> {
+        let mut payload = HashMap::new();
+        payload.insert("currency_pair", self.market_id(symbol)?);
+        let (spot, buy, sell) = tokio::try_join!(
+            self.get_data::<Price>(&Functionality::SpotPrice, payload.clone()),
+            self.get_data::<Price>(&Functionality::BuyPrice, payload.clone()),
+            self.get_data::<Price>(&Functionality::SellPrice, payload)
+        )?;
+        Ok(self.parse_ticker(symbol, &spot, &buy, &sell))
+    }
+
+    /// Coinbase has no batch price endpoint, every symbol is requested separately.
+    async fn fetch_tickers(&self, symbols: &[&str]) -> Result<BTreeMap<String, Ticker>> {
+        if symbols.is_empty() {
+            return Err(Error::ApiFunctionNotSupported("fetch_tickers without symbols"));
+        }
+        let mut tickers = BTreeMap::new();
+        for symbol in symbols {
+            tickers.insert(symbol.to_string(), self.fetch_ticker(symbol).await?);
+        }
+        Ok(tickers)
+    }
+}
+
+#[async_trait]
+impl Markets for Coinbase {
+    /// Synthetic crypto/fiat pairs, see `parse_markets`.
+    async fn fetch_markets(&self) -> Result<Vec<Market>> {
+        let fiat = self.get_data_no_params::<Vec<Currency>>(&Functionality::Assets).await?;
+        let crypto = self.get_data_no_params::<Vec<CryptoCurrency>>(&Functionality::CryptoAssets).await?;
+        Ok(self.parse_markets(&fiat, &crypto))
+    }
+
+    fn market_cache(&self) -> &RwLock<MarketCache> {
+        &self.markets
+    }
+}
+#[async_trait]
+impl SystemStatus for Coinbase {
+    async fn get_status(&self) -> Result<String> {
+        Err(Error::ApiFunctionNotSupported("get_status"))
+    }
+}
+
+#[async_trait]
+impl OrderBooks for Coinbase {
+    async fn fetch_order_book(&self, _symbol: &str, _limit: Option<u32>) -> Result<OrderBook> {
+        Err(Error::ApiFunctionNotSupported("fetch_order_book"))
+    }
+}
+
+#[async_trait]
+impl Candles for Coinbase {
+    async fn fetch_ohlcv(&self, _symbol: &str, _timeframe: Timeframe, _since: Option<DateTime>, _limit: Option<usize>) -> Result<Vec<Candle>> {
+        Err(Error::ApiFunctionNotSupported("fetch_ohlcv"))
+    }
+}
+
+#[async_trait]
+impl Trades for Coinbase {
+    async fn fetch_trades(&self, _symbol: &str, _since: Option<DateTime>, _limit: Option<usize>) -> Result<Vec<Trade>> {
+        Err(Error::ApiFunctionNotSupported("fetch_trades"))
+    }
+}
+
+#[async_trait]
+impl Trading for Coinbase {
+    async fn create_order(&self, _request: &OrderRequest) -> Result<Order> {
+        Err(Error::ApiFunctionNotSupported("create_order"))
+    }
+
+    async fn cancel_order(&self, _id: &str) -> Result<()> {
+        Err(Error::ApiFunctionNotSupported("cancel_order"))
+    }
+
+    async fn cancel_all_orders(&self, _symbol: Option<&str>) -> Result<BTreeMap<String, Result<()>>> {
+        Err(Error::ApiFunctionNotSupported("cancel_all_orders"))
+    }
+
+    async fn fetch_order(&self, _id: &str) -> Result<Order> {
+        Err(Error::ApiFunctionNotSupported("fetch_order"))
+    }
+
+    async fn fetch_orders(&self, _ids: &[&str]) -> Result<Vec<Order>> {
+        Err(Error::ApiFunctionNotSupported("fetch_orders"))
+    }
 
-        let res: Data<Time> = r.json::<Data<Time>>().await?;
-        println!("{:?}", res);
-        Ok(res.data.iso)
+    async fn fetch_open_orders(&self, _symbol: Option<&str>) -> Result<Vec<Order>> {
+        Err(Error::ApiFunctionNotSupported("fetch_open_orders"))
     }
-}
\ No newline at end of file
+
+    async fn fetch_closed_orders(&self, _symbol: Option<&str>, _since: Option<DateTime>, _limit: Option<usize>) -> Result<Vec<Order>> {
+        Err(Error::ApiFunctionNotSupported("fetch_closed_orders"))
+    }
+
+    async fn fetch_my_trades(&self, _symbol: Option<&str>, _since: Option<DateTime>, _limit: Option<usize>) -> Result<Vec<Trade>> {
+        Err(Error::ApiFunctionNotSupported("fetch_my_trades"))
+    }
+
+    async fn fetch_my_trades_by_ids(&self, _ids: &[&str]) -> Result<Vec<Trade>> {
+        Err(Error::ApiFunctionNotSupported("fetch_my_trades_by_ids"))
+    }
+}
+
+#[async_trait]
+impl TradingFees for Coinbase {
+    async fn fetch_trading_fees(&self, _symbols: &[&str]) -> Result<BTreeMap<String, TradingFee>> {
+        Err(Error::ApiFunctionNotSupported("fetch_trading_fees"))
+    }
+}
+
+impl UnifiedExchange for Coinbase {
+    fn exchange(&self) -> &Exchange {
+        &self.exchange
+    }
+
+    fn api(&self) -> &Api {
+        &self.api
+    }
+}
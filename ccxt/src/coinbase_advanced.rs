@@ -0,0 +1,1162 @@
+use std::{collections::{BTreeMap, HashMap}, convert::TryFrom, sync::{Arc, RwLock}};
+
+use async_trait::async_trait;
+use chrono::{Duration, TimeZone, Utc};
+use rand::Rng;
+use reqwest::StatusCode;
+use serde::{Deserialize, Deserializer, de::DeserializeOwned};
+use serde_json::json;
+use crate::{ApiRequest, DateTime, Decimal, Result, coinbase::{STATUS_ERRORS, sign}, errors::{ApiMessage, Error, ErrorTable}, exchange::*, rate_limiter::RateLimiter, retry::{self, RetryPolicy}};
+
+/// Advanced Trade error ids, matched as prefixes.
+const ERRORS: ErrorTable = &[
+    ("UNAUTHENTICATED", Error::AuthenticationError),
+    ("PERMISSION_DENIED", Error::PermissionDenied),
+    ("INSUFFICIENT_FUND", Error::InsufficientFunds),
+    ("PREVIEW_INSUFFICIENT_FUND", Error::InsufficientFunds),
+    ("UNKNOWN_CANCEL_ORDER", Error::OrderNotFound),
+    ("INVALID_LIMIT_PRICE", Error::InvalidOrder),
+    ("INVALID_PRICE_PRECISION", Error::InvalidOrder),
+    ("INVALID_SIZE_PRECISION", Error::InvalidOrder),
+    ("INVALID_NO_LIQUIDITY", Error::InvalidOrder),
+    ("INELIGIBLE_PAIR", Error::InvalidOrder),
+    ("UNSUPPORTED_ORDER_CONFIGURATION", Error::InvalidOrder),
+    ("PREVIEW_", Error::InvalidOrder),
+    ("ORDER_ENTRY_DISABLED", Error::OnMaintenance),
+    ("RESOURCE_EXHAUSTED", Error::RateLimitExceeded),
+    ("rate_limit_exceeded", Error::RateLimitExceeded),
+    ("UNAVAILABLE", Error::ExchangeNotAvailable),
+    ("INTERNAL", Error::ExchangeNotAvailable),
+];
+
+/// Maximum number of candles returned by a single candles request.
+const MAX_CANDLES: i32 = 350;
+
+/// Maximum number of trades returned by a single market trades request.
+const MAX_TRADES: usize = 1000;
+
+/// Maximum number of order ids accepted by a single batch cancel request.
+const MAX_CANCEL_IDS: usize = 100;
+
+/// Array query parameters, given comma separated in the payload and sent as repeated parameters.
+const LIST_PARAMS: [&str; 3] = ["order_status", "product_ids", "trade_ids"];
+
+/// Statuses of orders that no longer rest on the book.
+const CLOSED_STATUSES: [&str; 4] = ["FILLED", "CANCELLED", "EXPIRED", "FAILED"];
+
+/// Coinbase Advanced Trade (brokerage) API, authenticated with legacy HMAC API keys.
+#[derive(Debug, Clone)]
+pub struct CoinbaseAdvanced {
+    pub exchange: Exchange,
+    pub api: Api,
+    pub http_client: reqwest::Client,
+    pub rate_limiter: RateLimiter,
+    pub retry_policy: RetryPolicy,
+    markets: Arc<RwLock<MarketCache>>,
+}
+
+impl CoinbaseAdvanced {
+    /// Only legacy HMAC keys can sign requests. CDP keys (named `organizations/.../apiKeys/...`, with a PEM
+    /// private key as secret) need ES256 JWT signing and are rejected with `UnsupportedApiKey`.
+    pub fn new(id: &'static str, api_key: String, api_secret: String) -> Result<Self> {
+        if api_key.starts_with("organizations/") || api_secret.contains("PRIVATE KEY") {
+            return Err(Error::UnsupportedApiKey("CDP keys need JWT signing, use a legacy HMAC key"));
+        }
+        let exchange = Exchange::new(id, "Coinbase Advanced")
+            .rate_limit(100)
+            .user_agent(UserAgent::Chrome)
+            .countries(Country::UnitedStates);
+        Ok(CoinbaseAdvanced {
+            rate_limiter: RateLimiter::from_millis(exchange.rate_limit),
+            exchange,
+            api: Api::new("https://api.coinbase.com", "api/v3/brokerage")
+            .api_key(api_key)
+            .api_secret(api_secret)
+            .function(Functionality::Accounts,FunctionalityParams::new(AccessType::Private, Action::Get, "accounts"))
+            .function(Functionality::AddOrder,FunctionalityParams::new(AccessType::Private, Action::Post, "orders").non_idempotent())
+            .function(Functionality::AssetPairs,FunctionalityParams::new(AccessType::Public, Action::Get, "market/products"))
+            .function(Functionality::CancelOrder,FunctionalityParams::new(AccessType::Private, Action::Post, "orders/batch_cancel"))
+            .function(Functionality::ClosedOrders,FunctionalityParams::new(AccessType::Private, Action::Get, "orders/historical/batch"))
+            .function(Functionality::Depth,FunctionalityParams::new(AccessType::Public, Action::Get, "market/product_book"))
+            .function(Functionality::OHLC,FunctionalityParams::new(AccessType::Public, Action::Get, "market/products/{product_id}/candles"))
+            .function(Functionality::OpenOrders,FunctionalityParams::new(AccessType::Private, Action::Get, "orders/historical/batch"))
+            .function(Functionality::QueryOrders,FunctionalityParams::new(AccessType::Private, Action::Get, "orders/historical/{order_id}"))
+            .function(Functionality::QueryTrades,FunctionalityParams::new(AccessType::Private, Action::Get, "orders/historical/fills"))
+            .function(Functionality::Ticker,FunctionalityParams::new(AccessType::Public, Action::Get, "market/products/{product_id}/ticker"))
+            .function(Functionality::Time,FunctionalityParams::new(AccessType::Public, Action::Get, "time"))
+            .function(Functionality::TradeVolume,FunctionalityParams::new(AccessType::Private, Action::Get, "transaction_summary"))
+            .function(Functionality::Trades,FunctionalityParams::new(AccessType::Public, Action::Get, "market/products/{product_id}/ticker"))
+            .function(Functionality::TradesHistory,FunctionalityParams::new(AccessType::Private, Action::Get, "orders/historical/fills")),
+            http_client: reqwest::Client::new(),
+            retry_policy: RetryPolicy::default(),
+            markets: Arc::new(RwLock::new(MarketCache::default())),
+        })
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Translates an unified symbol (BTC/USD) into the product id (BTC-USD).
+    pub fn market_id(&self, symbol: &str) -> Result<String> {
+        if let Some(m) = self.markets.read().unwrap().market(symbol) {
+            return Ok(m.id.clone());
+        }
+        let (base, quote) = split_symbol(symbol)?;
+        Ok(format!("{}-{}", self.exchange.currency_id(base), self.exchange.currency_id(quote)))
+    }
+
+    /// Translates a product id (BTC-USD) into an unified symbol (BTC/USD).
+    pub fn symbol(&self, market_id: &str) -> String {
+        if let Some(m) = self.markets.read().unwrap().market_by_id(market_id) {
+            return m.symbol.clone();
+        }
+        match market_id.split_once('-') {
+            Some((base, quote)) => format!("{}/{}", self.exchange.common_currency_code(base), self.exchange.common_currency_code(quote)),
+            None => market_id.to_string()
+        }
+    }
+
+    /// Calls retry transient failures unless they are marked non idempotent.
+    pub async fn get_data<T>(&self, f: &Functionality, payload: HashMap<&str, String>) -> Result<T>
+    where T: DeserializeOwned
+    {
+        let fp = self.api.get_function_params(f)?;
+        if !fp.idempotent {
+            return self.send_data(fp, || self.get_request(f, payload.clone())).await;
+        }
+        self.retry_policy.retry(retry::is_transient, || self.send_data(fp, || self.get_request(f, payload.clone()))).await
+    }
+
+    /// Posts a JSON body, retried only when `idempotent` is set, e.g. for orders with a caller provided client order id.
+    pub async fn post_data<T>(&self, f: &Functionality, body: &serde_json::Value, idempotent: bool) -> Result<T>
+    where T: DeserializeOwned
+    {
+        let fp = self.api.get_function_params(f)?;
+        let path = self.get_uri_path(fp);
+        let request = || self.signed_request(fp, reqwest::Method::POST, &path, "", body.to_string());
+        if !idempotent {
+            return self.send_data(fp, request).await;
+        }
+        self.retry_policy.retry(retry::is_transient, || self.send_data(fp, request)).await
+    }
+
+    /// Follows the response cursor until every item, or at least `limit` items, are fetched.
+    pub async fn get_pages<P>(&self, f: &Functionality, payload: HashMap<&str, String>, limit: Option<usize>) -> Result<Vec<P::Item>>
+    where P: Page
+    {
+        let mut items = Vec::new();
+        let mut payload = payload;
+        loop {
+            let (page, cursor) = self.get_data::<P>(f, payload.clone()).await?.into_parts();
+            items.extend(page);
+            match cursor {
+                Some(c) if limit.is_none_or(|l| items.len() < l) => payload.insert("cursor", c),
+                _ => return Ok(items),
+            };
+        }
+    }
+
+    async fn send_data<T>(&self, fp: &FunctionalityParams, request: impl Fn() -> Result<ApiRequest>) -> Result<T>
+    where T: DeserializeOwned
+    {
+        self.rate_limiter.acquire(fp.cost).await;
+        let r = retry::error_for_transient_status(request()?.send().await?)?;
+        let status = r.status();
+        if !status.is_success() {
+            let body = r.text().await?;
+            return Err(self.parse_error(fp.uri_path, status, &body));
+        }
+        Ok(r.json::<T>().await?)
+    }
+
+    /// Adds the CB-ACCESS headers to private requests; the signed path excludes the query string.
+    fn signed_request(&self, fp: &FunctionalityParams, method: reqwest::Method, path: &str, query: &str, body: String) -> Result<ApiRequest> {
+        let url = if query.is_empty() {
+            format!("{}{}", self.api.url, path)
+        } else {
+            format!("{}{}?{}", self.api.url, path, query)
+        };
+        let req = self.http_client.request(method.clone(), url);
+        match fp.access_type {
+            AccessType::Public => Ok(req),
+            AccessType::Private => {
+                let key = self.api.key.as_deref()
+                    .filter(|k| !k.is_empty())
+                    .ok_or(Error::ArgumentsRequired("api key"))?;
+                let timestamp = Utc::now().timestamp().to_string();
+                let signature = sign(self.api.secret.as_deref().unwrap_or_default(), &timestamp, method.as_str(), path, &body);
+                let req = req.header("CB-ACCESS-KEY", key)
+                    .header("CB-ACCESS-SIGN", signature)
+                    .header("CB-ACCESS-TIMESTAMP", timestamp);
+                Ok(if body.is_empty() { req } else { req.header("Content-Type", "application/json").body(body) })
+            }
+        }
+    }
+
+    /// Maps a failed response onto an error variant by its error id, or by the HTTP status.
+    pub fn parse_error(&self, endpoint: &'static str, status: StatusCode, body: &str) -> Error {
+        let (id, message) = match serde_json::from_str::<ErrorInfo>(body) {
+            Ok(e) => (e.error.clone(), e.message().unwrap_or(body).to_string()),
+            Err(_) => (String::new(), body.to_string()),
+        };
+        match Error::from_table(ERRORS, &id, endpoint, &message) {
+            Error::ApiCallError(_) => Error::from_table(STATUS_ERRORS, status.as_str(), endpoint, &message),
+            e => e,
+        }
+    }
+
+    /// Products carry no fee rates, `fetch_trading_fees` fills in taker and maker of the cached markets.
+    pub fn parse_markets(&self, products: &[Product]) -> Vec<Market> {
+        products.iter()
+            .filter(|p| p.product_type.is_empty() || p.product_type == "SPOT")
+            .map(|p| {
+                let base = self.exchange.common_currency_code(&p.base_currency_id);
+                let quote = self.exchange.common_currency_code(&p.quote_currency_id);
+                Market {
+                    id: p.product_id.clone(),
+                    symbol: format!("{}/{}", base, quote),
+                    base,
+                    quote,
+                    base_id: p.base_currency_id.clone(),
+                    quote_id: p.quote_currency_id.clone(),
+                    active: Some(!p.trading_disabled && !p.is_disabled && (p.status.is_empty() || p.status == "online")),
+                    precision: MarketPrecision {
+                        price: Some(p.price_increment.unwrap_or(p.quote_increment).normalize().scale()),
+                        amount: Some(p.base_increment.normalize().scale()),
+                        cost: Some(p.quote_increment.normalize().scale()),
+                    },
+                    limits: MarketLimits {
+                        amount: MinMax { min: p.base_min_size, max: p.base_max_size },
+                        cost: MinMax { min: p.quote_min_size, max: p.quote_max_size },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// Best bid and ask with the last trade price.
+    pub fn parse_ticker(&self, symbol: &str, trades: &MarketTrades) -> Ticker {
+        let last = trades.trades.first();
+        Ticker {
+            symbol: symbol.to_string(),
+            timestamp: Some(last.map_or_else(Utc::now, |t| t.time)),
+            bid: trades.best_bid,
+            ask: trades.best_ask,
+            last: last.map(|t| t.price),
+            ..Default::default()
+        }
+    }
+
+    /// Product listings carry the last price and 24 hour statistics, but no bid and ask.
+    pub fn parse_product_ticker(&self, product: &Product) -> Ticker {
+        Ticker {
+            symbol: self.symbol(&product.product_id),
+            timestamp: Some(Utc::now()),
+            last: product.price,
+            percentage: product.price_percentage_change_24h,
+            base_volume: product.volume_24h,
+            ..Default::default()
+        }
+    }
+
+    pub fn parse_order_book(&self, symbol: &str, book: &Pricebook) -> OrderBook {
+        let levels = |l: &[BookLevel]| l.iter().map(|l| OrderBookLevel { price: l.price, amount: l.size }).collect();
+        let mut result = OrderBook {
+            symbol: symbol.to_string(),
+            bids: levels(&book.bids),
+            asks: levels(&book.asks),
+            timestamp: book.time,
+            nonce: None,
+        };
+        result.sort();
+        result
+    }
+
+    pub fn parse_candle(&self, candle: &CandleInfo) -> Option<Candle> {
+        Some(Candle {
+            timestamp: Utc.timestamp_opt(candle.start.parse().ok()?, 0).single()?,
+            open: candle.open,
+            high: candle.high,
+            low: candle.low,
+            close: candle.close,
+            volume: candle.volume,
+        })
+    }
+
+    pub fn parse_trade(&self, trade: &MarketTrade) -> Trade {
+        Trade {
+            id: Some(trade.trade_id.clone()),
+            symbol: self.symbol(&trade.product_id),
+            timestamp: trade.time,
+            side: side_from_str(&trade.side),
+            price: trade.price,
+            amount: trade.size,
+            cost: trade.price * trade.size,
+            order_type: None,
+            order_id: None,
+            fee: None,
+            fee_currency: None,
+            taker_or_maker: None,
+        }
+    }
+
+    /// Fills sized in quote currency are converted to base amounts.
+    pub fn parse_fill(&self, fill: &Fill) -> Trade {
+        let symbol = self.symbol(&fill.product_id);
+        let (amount, cost) = if fill.size_in_quote {
+            (fill.size / fill.price, fill.size)
+        } else {
+            (fill.size, fill.size * fill.price)
+        };
+        Trade {
+            id: Some(fill.trade_id.clone()),
+            timestamp: fill.trade_time,
+            side: side_from_str(&fill.side),
+            price: fill.price,
+            amount,
+            cost,
+            order_type: None,
+            order_id: Some(fill.order_id.clone()),
+            fee: Some(fill.commission),
+            fee_currency: split_symbol(&symbol).ok().map(|(_, quote)| quote.to_string()),
+            taker_or_maker: match fill.liquidity_indicator.as_str() {
+                "MAKER" => Some(TakerOrMaker::Maker),
+                "TAKER" => Some(TakerOrMaker::Taker),
+                _ => None,
+            },
+            symbol,
+        }
+    }
+
+    pub fn parse_balance(&self, accounts: &[AccountInfo]) -> AccountBalance {
+        let mut result = AccountBalance::default();
+        for a in accounts {
+            let used = a.hold.as_ref().map_or(Decimal::ZERO, |h| h.value);
+            result.add(self.exchange.common_currency_code(&a.currency), a.available_balance.value + used, used);
+        }
+        result
+    }
+
+    /// Order configuration of the request; a client order id is generated when none is given.
+    pub fn order_body(&self, request: &OrderRequest) -> Result<serde_json::Value> {
+        if request.leverage.is_some() {
+            return Err(Error::ApiFunctionNotSupported("leverage"));
+        }
+        let market = self.markets.read().unwrap().market(&request.symbol).cloned();
+        let amount = market.as_ref().map_or(request.amount, |m| m.amount_to_precision(request.amount)).to_string();
+        let price = |p: Option<Decimal>, name: &'static str| -> Result<String> {
+            let p = p.ok_or(Error::ArgumentsRequired(name))?;
+            Ok(market.as_ref().map_or(p, |m| m.price_to_precision(p)).to_string())
+        };
+        let configuration = match (request.order_type, request.time_in_force) {
+            (OrderType::Market, _) => json!({ "market_market_ioc": { "base_size": amount } }),
+            (OrderType::Limit, Some(TimeInForce::ImmediateOrCancel)) => json!({
+                "sor_limit_ioc": { "base_size": amount, "limit_price": price(request.price, "price")? }
+            }),
+            (OrderType::Limit, Some(TimeInForce::GoodTillDate(end))) => json!({
+                "limit_limit_gtd": { "base_size": amount, "limit_price": price(request.price, "price")?, "end_time": end.to_rfc3339(), "post_only": request.post_only }
+            }),
+            (OrderType::Limit, _) => json!({
+                "limit_limit_gtc": { "base_size": amount, "limit_price": price(request.price, "price")?, "post_only": request.post_only }
+            }),
+            (OrderType::StopLossLimit, _) | (OrderType::TakeProfitLimit, _) => {
+                let stop_loss = request.order_type == OrderType::StopLossLimit;
+                let direction = if stop_loss == (request.side == Side::Sell) { "STOP_DIRECTION_STOP_DOWN" } else { "STOP_DIRECTION_STOP_UP" };
+                json!({
+                    "stop_limit_stop_limit_gtc": {
+                        "base_size": amount,
+                        "limit_price": price(request.price, "price")?,
+                        "stop_price": price(request.stop_price, "stop_price")?,
+                        "stop_direction": direction
+                    }
+                })
+            },
+            (OrderType::StopLoss, _) | (OrderType::TakeProfit, _) => return Err(Error::ApiFunctionNotSupported("stop market orders")),
+        };
+        let client_order_id = request.client_order_id.clone()
+            .unwrap_or_else(|| format!("{:032x}", rand::thread_rng().gen::<u128>()));
+        Ok(json!({
+            "client_order_id": client_order_id,
+            "product_id": self.market_id(&request.symbol)?,
+            "side": if request.side == Side::Buy { "BUY" } else { "SELL" },
+            "order_configuration": configuration,
+        }))
+    }
+
+    pub fn parse_order(&self, info: &OrderInfo) -> Order {
+        let side = side_from_str(&info.side);
+        let c = &info.order_configuration;
+        let (order_type, time_in_force, params) = if let Some(p) = &c.market_market_ioc {
+            (OrderType::Market, Some(TimeInForce::ImmediateOrCancel), p.clone())
+        } else if let Some(p) = &c.sor_limit_ioc {
+            (OrderType::Limit, Some(TimeInForce::ImmediateOrCancel), p.clone())
+        } else if let Some(p) = &c.limit_limit_gtc {
+            (OrderType::Limit, Some(TimeInForce::GoodTillCancel), p.clone())
+        } else if let Some(p) = &c.limit_limit_gtd {
+            (OrderType::Limit, p.end_time.map(TimeInForce::GoodTillDate), p.clone())
+        } else if let Some(p) = c.stop_limit_stop_limit_gtc.as_ref().or(c.stop_limit_stop_limit_gtd.as_ref()) {
+            let stop_down = p.stop_direction.as_deref() == Some("STOP_DIRECTION_STOP_DOWN");
+            let order_type = if stop_down == (side == Side::Sell) { OrderType::StopLossLimit } else { OrderType::TakeProfitLimit };
+            (order_type, p.end_time.map(TimeInForce::GoodTillDate).or(Some(TimeInForce::GoodTillCancel)), p.clone())
+        } else {
+            (OrderType::Limit, None, c.limit_limit_fok.clone().unwrap_or_default())
+        };
+        let filled = info.filled_size.unwrap_or_default();
+        let amount = params.base_size.unwrap_or(filled);
+        Order {
+            id: info.order_id.clone(),
+            client_order_id: Some(info.client_order_id.clone()).filter(|id| !id.is_empty()),
+            symbol: self.symbol(&info.product_id),
+            timestamp: info.created_time,
+            status: order_status_from_str(&info.status),
+            order_type,
+            side,
+            time_in_force,
+            post_only: params.post_only.unwrap_or(false),
+            price: params.limit_price,
+            stop_price: params.stop_price,
+            amount,
+            filled,
+            remaining: (amount - filled).max(Decimal::ZERO),
+            cost: info.filled_value.unwrap_or_default(),
+            average: info.average_filled_price.filter(|p| !p.is_zero()),
+            fee: info.total_fees,
+            description: None,
+        }
+    }
+
+    /// The fee tier applies to every product.
+    pub fn parse_trading_fees(&self, summary: &TransactionSummary, symbols: &[String]) -> BTreeMap<String, TradingFee> {
+        let tier_volume = summary.fee_tier.usd_from.replace(',', "").parse::<Decimal>().ok();
+        symbols.iter()
+            .map(|s| (s.clone(), TradingFee {
+                symbol: s.clone(),
+                maker: summary.fee_tier.maker_fee_rate,
+                taker: summary.fee_tier.taker_fee_rate,
+                volume: Some(summary.total_volume),
+                volume_currency: Some("USD".to_string()),
+                tier_volume,
+            }))
+            .collect()
+    }
+
+    /// Orders of the product in any of `statuses`, most recent first.
+    async fn fetch_order_list(&self, f: &Functionality, symbol: Option<&str>, statuses: &[&str], since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Order>> {
+        let mut payload = HashMap::new();
+        if let Some(s) = symbol {
+            payload.insert("product_ids", self.market_id(s)?);
+        }
+        if !statuses.is_empty() {
+            payload.insert("order_status", statuses.join(","));
+        }
+        if let Some(s) = since {
+            payload.insert("start_date", s.to_rfc3339());
+        }
+        let orders = self.get_pages::<OrdersResult>(f, payload, limit).await?;
+        Ok(orders.iter().map(|o| self.parse_order(o)).collect())
+    }
+}
+
+/// Query string with the `LIST_PARAMS` split into one parameter per value.
+fn encode_query(payload: &HashMap<&str, String>) -> String {
+    let (lists, others): (HashMap<&str, String>, HashMap<&str, String>) = payload.clone().into_iter()
+        .partition(|(k, _)| LIST_PARAMS.contains(k));
+    let mut params = lists.iter()
+        .flat_map(|(name, values)| values.split(',').map(move |v| Api::encode_component(name) + "=" + &Api::encode_component(v)))
+        .collect::<Vec<_>>();
+    if !others.is_empty() {
+        params.push(Api::encode_uri(&others));
+    }
+    params.join("&")
+}
+
+fn side_from_str(side: &str) -> Side {
+    if side.eq_ignore_ascii_case("sell") { Side::Sell } else { Side::Buy }
+}
+
+fn order_status_from_str(status: &str) -> OrderStatus {
+    match status {
+        "PENDING" | "QUEUED" => OrderStatus::Pending,
+        "FILLED" => OrderStatus::Closed,
+        "CANCELLED" => OrderStatus::Canceled,
+        "EXPIRED" => OrderStatus::Expired,
+        "FAILED" => OrderStatus::Rejected,
+        _ => OrderStatus::Open,
+    }
+}
+
+/// Candle granularity names, Coinbase has no 4 hour, weekly or 15 day candles.
+fn granularity(timeframe: Timeframe) -> Result<&'static str> {
+    match timeframe {
+        Timeframe::Minute1 => Ok("ONE_MINUTE"),
+        Timeframe::Minute5 => Ok("FIVE_MINUTE"),
+        Timeframe::Minute15 => Ok("FIFTEEN_MINUTE"),
+        Timeframe::Minute30 => Ok("THIRTY_MINUTE"),
+        Timeframe::Hour1 => Ok("ONE_HOUR"),
+        Timeframe::Day1 => Ok("ONE_DAY"),
+        _ => Err(Error::ApiFunctionNotSupported("timeframe")),
+    }
+}
+
+/// Decimal sent as a string that is empty when the value is not known.
+fn empty_decimal<'de, D>(deserializer: D) -> std::result::Result<Option<Decimal>, D::Error>
+where D: Deserializer<'de>
+{
+    match Option::<String>::deserialize(deserializer)?.as_deref() {
+        None | Some("") => Ok(None),
+        Some(s) => s.parse::<Decimal>().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+/// One page of a cursor paginated response.
+pub trait Page: DeserializeOwned {
+    type Item;
+
+    /// Items of the page and the cursor of the next page.
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>);
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorInfo {
+    #[serde(default)]
+    pub error: String,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub error_details: Option<String>,
+}
+
+impl ErrorInfo {
+    fn message(&self) -> Option<&str> {
+        self.message.as_deref().filter(|m| !m.is_empty())
+            .or_else(|| self.error_details.as_deref().filter(|m| !m.is_empty()))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ServerTime {
+    pub iso: DateTime,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Product {
+    pub product_id: String,
+    pub base_currency_id: String,
+    pub quote_currency_id: String,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub price: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub price_percentage_change_24h: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub volume_24h: Option<Decimal>,
+    pub base_increment: Decimal,
+    pub quote_increment: Decimal,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub price_increment: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub base_min_size: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub base_max_size: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub quote_min_size: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub quote_max_size: Option<Decimal>,
+    #[serde(default)]
+    pub trading_disabled: bool,
+    #[serde(default)]
+    pub is_disabled: bool,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub product_type: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ProductsResult {
+    pub products: Vec<Product>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BookLevel {
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Pricebook {
+    pub product_id: String,
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+    pub time: Option<DateTime>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ProductBookResult {
+    pub pricebook: Pricebook,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CandleInfo {
+    /// unix time in seconds
+    pub start: String,
+    pub low: Decimal,
+    pub high: Decimal,
+    pub open: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CandlesResult {
+    pub candles: Vec<CandleInfo>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MarketTrade {
+    pub trade_id: String,
+    pub product_id: String,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub time: DateTime,
+    pub side: String,
+}
+
+/// Most recent trades first, with the current best bid and ask.
+#[derive(Deserialize, Debug)]
+pub struct MarketTrades {
+    pub trades: Vec<MarketTrade>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub best_bid: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub best_ask: Option<Decimal>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Amount {
+    pub value: Decimal,
+    pub currency: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AccountInfo {
+    pub uuid: String,
+    pub currency: String,
+    pub available_balance: Amount,
+    pub hold: Option<Amount>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AccountsResult {
+    pub accounts: Vec<AccountInfo>,
+    #[serde(default)]
+    pub has_next: bool,
+    #[serde(default)]
+    pub cursor: String,
+}
+
+impl Page for AccountsResult {
+    type Item = AccountInfo;
+
+    fn into_parts(self) -> (Vec<AccountInfo>, Option<String>) {
+        let has_next = self.has_next;
+        let cursor = Some(self.cursor).filter(|c| has_next && !c.is_empty());
+        (self.accounts, cursor)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct OrderParams {
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub base_size: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub quote_size: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub limit_price: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub stop_price: Option<Decimal>,
+    pub stop_direction: Option<String>,
+    pub end_time: Option<DateTime>,
+    pub post_only: Option<bool>,
+}
+
+/// Exactly one of the configurations is set.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct OrderConfiguration {
+    pub market_market_ioc: Option<OrderParams>,
+    pub sor_limit_ioc: Option<OrderParams>,
+    pub limit_limit_gtc: Option<OrderParams>,
+    pub limit_limit_gtd: Option<OrderParams>,
+    pub limit_limit_fok: Option<OrderParams>,
+    pub stop_limit_stop_limit_gtc: Option<OrderParams>,
+    pub stop_limit_stop_limit_gtd: Option<OrderParams>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OrderInfo {
+    pub order_id: String,
+    pub product_id: String,
+    pub side: String,
+    #[serde(default)]
+    pub client_order_id: String,
+    pub status: String,
+    pub created_time: Option<DateTime>,
+    #[serde(default)]
+    pub order_configuration: OrderConfiguration,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub filled_size: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub filled_value: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub average_filled_price: Option<Decimal>,
+    #[serde(default, deserialize_with = "empty_decimal")]
+    pub total_fees: Option<Decimal>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HistoricalOrderResult {
+    pub order: OrderInfo,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OrdersResult {
+    pub orders: Vec<OrderInfo>,
+    #[serde(default)]
+    pub has_next: bool,
+    #[serde(default)]
+    pub cursor: String,
+}
+
+impl Page for OrdersResult {
+    type Item = OrderInfo;
+
+    fn into_parts(self) -> (Vec<OrderInfo>, Option<String>) {
+        let has_next = self.has_next;
+        let cursor = Some(self.cursor).filter(|c| has_next && !c.is_empty());
+        (self.orders, cursor)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Fill {
+    pub trade_id: String,
+    pub order_id: String,
+    pub product_id: String,
+    pub trade_time: DateTime,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub commission: Decimal,
+    pub side: String,
+    #[serde(default)]
+    pub liquidity_indicator: String,
+    #[serde(default)]
+    pub size_in_quote: bool,
+}
+
+/// The cursor is empty on the last page.
+#[derive(Deserialize, Debug)]
+pub struct FillsResult {
+    pub fills: Vec<Fill>,
+    #[serde(default)]
+    pub cursor: String,
+}
+
+impl Page for FillsResult {
+    type Item = Fill;
+
+    fn into_parts(self) -> (Vec<Fill>, Option<String>) {
+        let cursor = Some(self.cursor).filter(|c| !c.is_empty());
+        (self.fills, cursor)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OrderSuccess {
+    pub order_id: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OrderFailure {
+    #[serde(default)]
+    pub error: String,
+    #[serde(default)]
+    pub message: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateOrderResult {
+    pub success: bool,
+    pub success_response: Option<OrderSuccess>,
+    pub error_response: Option<OrderFailure>,
+    #[serde(default)]
+    pub failure_reason: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CancelResult {
+    pub success: bool,
+    #[serde(default)]
+    pub failure_reason: String,
+    pub order_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CancelOrdersResult {
+    pub results: Vec<CancelResult>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FeeTierInfo {
+    #[serde(default)]
+    pub pricing_tier: String,
+    /// 30 day volume in USD where the tier starts, may contain thousands separators
+    #[serde(default)]
+    pub usd_from: String,
+    pub taker_fee_rate: Decimal,
+    pub maker_fee_rate: Decimal,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TransactionSummary {
+    pub total_volume: Decimal,
+    pub fee_tier: FeeTierInfo,
+}
+
+#[async_trait]
+impl crate::exchange::ServerTime for CoinbaseAdvanced {
+    async fn get_time(&self) -> Result<DateTime> {
+        let time = self.get_data::<ServerTime>(&Functionality::Time, HashMap::new()).await?;
+        Ok(time.iso)
+    }
+}
+
+#[async_trait]
+impl SystemStatus for CoinbaseAdvanced {
+    /// The brokerage API has no status endpoint, `has()` reports `get_status` as absent.
+    async fn get_status(&self) -> Result<String> {
+        Err(Error::ApiFunctionNotSupported("get_status"))
+    }
+}
+
+#[async_trait]
+impl Balance for CoinbaseAdvanced {
+    async fn get_balance(&self) -> Result<AccountBalance> {
+        let mut payload = HashMap::new();
+        payload.insert("limit", "250".to_string());
+        let accounts = self.get_pages::<AccountsResult>(&Functionality::Accounts, payload, None).await?;
+        Ok(self.parse_balance(&accounts))
+    }
+}
+
+#[async_trait]
+impl Markets for CoinbaseAdvanced {
+    async fn fetch_markets(&self) -> Result<Vec<Market>> {
+        let result = self.get_data::<ProductsResult>(&Functionality::AssetPairs, HashMap::new()).await?;
+        Ok(self.parse_markets(&result.products))
+    }
+
+    fn market_cache(&self) -> &RwLock<MarketCache> {
+        &self.markets
+    }
+}
+
+#[async_trait]
+impl Tickers for CoinbaseAdvanced {
+    async fn fetch_ticker(&self, symbol: &str) -> Result<Ticker> {
+        let mut payload = HashMap::new();
+        payload.insert("product_id", self.market_id(symbol)?);
+        payload.insert("limit", "1".to_string());
+        let trades = self.get_data::<MarketTrades>(&Functionality::Ticker, payload).await?;
+        Ok(self.parse_ticker(symbol, &trades))
+    }
+
+    /// Tickers of every product are built from one product listing, without bid and ask.
+    async fn fetch_tickers(&self, symbols: &[&str]) -> Result<BTreeMap<String, Ticker>> {
+        self.load_markets(false).await?;
+        let ids = symbols.iter().map(|s| self.market_id(s)).collect::<Result<Vec<_>>>()?;
+        let result = self.get_data::<ProductsResult>(&Functionality::AssetPairs, HashMap::new()).await?;
+        Ok(result.products.iter()
+            .filter(|p| ids.is_empty() || ids.contains(&p.product_id))
+            .map(|p| self.parse_product_ticker(p))
+            .map(|t| (t.symbol.clone(), t))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl OrderBooks for CoinbaseAdvanced {
+    async fn fetch_order_book(&self, symbol: &str, limit: Option<u32>) -> Result<OrderBook> {
+        let mut payload = HashMap::new();
+        payload.insert("product_id", self.market_id(symbol)?);
+        if let Some(l) = limit {
+            payload.insert("limit", l.to_string());
+        }
+        let result = self.get_data::<ProductBookResult>(&Functionality::Depth, payload).await?;
+        Ok(self.parse_order_book(symbol, &result.pricebook))
+    }
+}
+
+#[async_trait]
+impl Candles for CoinbaseAdvanced {
+    /// Candles are requested in windows of at most 350 candles. Without `since` the first window
+    /// starts `limit` candles ago, but not before the Unix epoch.
+    async fn fetch_ohlcv(&self, symbol: &str, timeframe: Timeframe, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Candle>> {
+        let granularity = granularity(timeframe)?;
+        let step = Duration::minutes(i64::from(timeframe.minutes()));
+        let count = limit.map_or(MAX_CANDLES, |l| i32::try_from(l).unwrap_or(i32::MAX));
+        let now = Utc::now();
+        let epoch = Utc.timestamp_opt(0, 0).unwrap();
+        let mut start = since.unwrap_or_else(|| step.checked_mul(count)
+            .and_then(|span| now.checked_sub_signed(span))
+            .map_or(epoch, |s| s.max(epoch)));
+        let mut candles = BTreeMap::new();
+        while start < now && limit.is_none_or(|l| candles.len() < l) {
+            let end = (start + step * MAX_CANDLES).min(now);
+            let mut payload = HashMap::new();
+            payload.insert("product_id", self.market_id(symbol)?);
+            payload.insert("granularity", granularity.to_string());
+            payload.insert("start", start.timestamp().to_string());
+            payload.insert("end", end.timestamp().to_string());
+            let result = self.get_data::<CandlesResult>(&Functionality::OHLC, payload).await?;
+            for c in result.candles.iter().filter_map(|c| self.parse_candle(c)) {
+                candles.insert(c.timestamp, c);
+            }
+            start = end;
+        }
+        let mut candles = candles.into_values().collect::<Vec<_>>();
+        limit_items(&mut candles, since, limit);
+        Ok(candles)
+    }
+}
+
+#[async_trait]
+impl Trades for CoinbaseAdvanced {
+    /// Coinbase returns the most recent trades of the `start`/`end` window, at most 1000 per request.
+    /// The window end is moved back to the oldest trade received until a page reaches `since`,
+    /// or without `since` until `limit` trades are collected.
+    async fn fetch_trades(&self, symbol: &str, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Trade>> {
+        let page_size = if since.is_none() { limit.unwrap_or(MAX_TRADES).min(MAX_TRADES) } else { MAX_TRADES };
+        let mut trades = HashMap::new();
+        let mut end = since.map(|_| Utc::now().timestamp());
+        loop {
+            let mut payload = HashMap::new();
+            payload.insert("product_id", self.market_id(symbol)?);
+            payload.insert("limit", page_size.to_string());
+            if let Some(s) = since {
+                payload.insert("start", s.timestamp().to_string());
+            }
+            if let Some(e) = end {
+                payload.insert("end", e.to_string());
+            }
+            let result = self.get_data::<MarketTrades>(&Functionality::Trades, payload).await?;
+            let oldest = result.trades.iter().map(|t| t.time.timestamp()).min();
+            let full = result.trades.len() >= page_size;
+            for t in result.trades.iter().map(|t| self.parse_trade(t)).filter(|t| since.is_none_or(|s| t.timestamp >= s)) {
+                trades.insert(t.id.clone(), t);
+            }
+            let enough = since.is_none() && limit.is_none_or(|l| trades.len() >= l);
+            match oldest {
+                // trades of the oldest second may continue on the next page, duplicates are dropped by id
+                Some(o) if full && !enough => end = Some(end.map_or(o, |e| o.min(e - 1))),
+                _ => break,
+            }
+        }
+        let mut trades = trades.into_values().collect::<Vec<_>>();
+        trades.sort_by_key(|t| t.timestamp);
+        limit_items(&mut trades, since, limit);
+        Ok(trades)
+    }
+}
+
+#[async_trait]
+impl Trading for CoinbaseAdvanced {
+    /// Retried on transient failures only when the request carries a client order id,
+    /// Coinbase then returns the existing order instead of placing a duplicate.
+    async fn create_order(&self, request: &OrderRequest) -> Result<Order> {
+        if request.validate {
+            return Err(Error::ApiFunctionNotSupported("order validation"));
+        }
+        self.load_markets(false).await?;
+        let body = self.order_body(request)?;
+        let result = self.post_data::<CreateOrderResult>(&Functionality::AddOrder, &body, request.client_order_id.is_some()).await?;
+        let endpoint = self.api.get_function_params(&Functionality::AddOrder)?.uri_path;
+        match (result.success, result.success_response) {
+            (true, Some(r)) => Ok(Order {
+                id: r.order_id,
+                client_order_id: body["client_order_id"].as_str().map(|s| s.to_string()),
+                symbol: request.symbol.clone(),
+                timestamp: Some(Utc::now()),
+                status: OrderStatus::Open,
+                order_type: request.order_type,
+                side: request.side,
+                time_in_force: request.time_in_force,
+                post_only: request.post_only,
+                price: request.price,
+                stop_price: request.stop_price,
+                amount: request.amount,
+                filled: Decimal::ZERO,
+                remaining: request.amount,
+                cost: Decimal::ZERO,
+                average: None,
+                fee: None,
+                description: None,
+            }),
+            _ => {
+                let failure = result.error_response.unwrap_or(OrderFailure { error: result.failure_reason.clone(), message: result.failure_reason });
+                Err(Error::from_table(ERRORS, &failure.error, endpoint, &failure.message))
+            },
+        }
+    }
+
+    async fn cancel_order(&self, id: &str) -> Result<()> {
+        let endpoint = self.api.get_function_params(&Functionality::CancelOrder)?.uri_path;
+        let result = self.post_data::<CancelOrdersResult>(&Functionality::CancelOrder, &json!({ "order_ids": [id] }), true).await?;
+        match result.results.into_iter().find(|r| r.order_id == id) {
+            Some(r) if r.success => Ok(()),
+            Some(r) => Err(Error::from_table(ERRORS, &r.failure_reason, endpoint, &r.failure_reason)),
+            None => Err(Error::ApiCallNoData()),
+        }
+    }
+
+    /// Open orders are canceled in batches of up to 100 ids. A failed batch request is reported for each of its orders,
+    /// the other batches are still sent.
+    async fn cancel_all_orders(&self, symbol: Option<&str>) -> Result<BTreeMap<String, Result<()>>> {
+        let ids = self.fetch_open_orders(symbol).await?.into_iter().map(|o| o.id).collect::<Vec<_>>();
+        let endpoint = self.api.get_function_params(&Functionality::CancelOrder)?.uri_path;
+        let mut results = BTreeMap::new();
+        for chunk in ids.chunks(MAX_CANCEL_IDS) {
+            match self.post_data::<CancelOrdersResult>(&Functionality::CancelOrder, &json!({ "order_ids": chunk }), true).await {
+                Ok(result) => for r in result.results {
+                    let outcome = if r.success { Ok(()) } else { Err(Error::from_table(ERRORS, &r.failure_reason, endpoint, &r.failure_reason)) };
+                    results.insert(r.order_id, outcome);
+                },
+                Err(e) => for id in chunk {
+                    results.insert(id.clone(), Err(Error::ApiCallError(ApiMessage::new(endpoint, &e.to_string()))));
+                },
+            }
+            for id in chunk {
+                results.entry(id.clone()).or_insert(Err(Error::ApiCallNoData()));
+            }
+        }
+        Ok(results)
+    }
+
+    async fn fetch_order(&self, id: &str) -> Result<Order> {
+        let mut payload = HashMap::new();
+        payload.insert("order_id", id.to_string());
+        let result = self.get_data::<HistoricalOrderResult>(&Functionality::QueryOrders, payload).await?;
+        Ok(self.parse_order(&result.order))
+    }
+
+    async fn fetch_orders(&self, ids: &[&str]) -> Result<Vec<Order>> {
+        let mut orders = Vec::with_capacity(ids.len());
+        for id in ids {
+            orders.push(self.fetch_order(id).await?);
+        }
+        Ok(orders)
+    }
+
+    async fn fetch_open_orders(&self, symbol: Option<&str>) -> Result<Vec<Order>> {
+        let mut orders = self.fetch_order_list(&Functionality::OpenOrders, symbol, &["OPEN"], None, None).await?;
+        orders.sort_by_key(|o| o.timestamp);
+        Ok(orders)
+    }
+
+    /// Orders are returned newest first. Without `since` pages are requested until `limit` orders are
+    /// collected; with `since` every page back to `since` is requested, the oldest come last.
+    async fn fetch_closed_orders(&self, symbol: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Order>> {
+        let page_limit = if since.is_none() { limit } else { None };
+        let mut orders = self.fetch_order_list(&Functionality::ClosedOrders, symbol, &CLOSED_STATUSES, since, page_limit).await?;
+        orders.sort_by_key(|o| o.timestamp);
+        limit_items(&mut orders, since, limit);
+        Ok(orders)
+    }
+
+    /// Fills are returned newest first. Without `since` pages are requested until `limit` fills are
+    /// collected; with `since` every page back to `since` is requested, the oldest come last.
+    async fn fetch_my_trades(&self, symbol: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Trade>> {
+        let mut payload = HashMap::new();
+        if let Some(s) = symbol {
+            payload.insert("product_ids", self.market_id(s)?);
+        }
+        if let Some(s) = since {
+            payload.insert("start_sequence_timestamp", s.to_rfc3339());
+        }
+        let page_limit = if since.is_none() { limit } else { None };
+        let fills = self.get_pages::<FillsResult>(&Functionality::TradesHistory, payload, page_limit).await?;
+        let mut trades = fills.iter().map(|f| self.parse_fill(f)).collect::<Vec<_>>();
+        trades.sort_by_key(|t| t.timestamp);
+        limit_items(&mut trades, since, limit);
+        Ok(trades)
+    }
+
+    async fn fetch_my_trades_by_ids(&self, ids: &[&str]) -> Result<Vec<Trade>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut payload = HashMap::new();
+        payload.insert("trade_ids", ids.join(","));
+        let fills = self.get_pages::<FillsResult>(&Functionality::QueryTrades, payload, None).await?;
+        Ok(fills.iter().map(|f| self.parse_fill(f)).collect())
+    }
+}
+
+#[async_trait]
+impl TradingFees for CoinbaseAdvanced {
+    /// Fees of every loaded market when no symbols are given. The rates are stored on the cached
+    /// markets as well, so `calculate_fee` can use them.
+    async fn fetch_trading_fees(&self, symbols: &[&str]) -> Result<BTreeMap<String, TradingFee>> {
+        let markets = self.load_markets(false).await?;
+        let symbols = if symbols.is_empty() {
+            markets.into_keys().collect()
+        } else {
+            symbols.iter().map(|s| s.to_string()).collect::<Vec<_>>()
+        };
+        let summary = self.get_data::<TransactionSummary>(&Functionality::TradeVolume, HashMap::new()).await?;
+        let fees = self.parse_trading_fees(&summary, &symbols);
+        let mut cache = self.markets.write().unwrap();
+        for fee in fees.values() {
+            if let Some(m) = cache.market_mut(&fee.symbol) {
+                m.taker = Some(fee.taker);
+                m.maker = Some(fee.maker);
+            }
+        }
+        Ok(fees)
+    }
+}
+
+impl ApiCalls for CoinbaseAdvanced {
+
+    fn get_url(&self, params: &FunctionalityParams) -> String {
+        format!("{}{}", self.api.url, self.get_uri_path(params))
+    }
+
+    fn get_uri_path(&self, params: &FunctionalityParams) -> String {
+        format!("/{}/{}", self.api.version, params.uri_path)
+    }
+
+    /// GET sends the payload as query string, POST as a flat JSON body.
+    fn get_request(&self, f: &Functionality, mut payload: HashMap<&str, String>) -> Result<ApiRequest>
+    {
+        let fp = self.api.get_function_params(f)?;
+        let path = Api::expand_uri_path(&self.get_uri_path(fp), &mut payload);
+        match fp.action {
+            Action::Post => self.signed_request(fp, reqwest::Method::POST, &path, "", serde_json::to_string(&payload)?),
+            Action::Get => self.signed_request(fp, reqwest::Method::GET, &path, &encode_query(&payload), String::new()),
+            Action::Delete => self.signed_request(fp, reqwest::Method::DELETE, &path, &encode_query(&payload), String::new()),
+        }
+    }
+}
+
+impl UnifiedExchange for CoinbaseAdvanced {
+    fn exchange(&self) -> &Exchange {
+        &self.exchange
+    }
+
+    fn api(&self) -> &Api {
+        &self.api
+    }
+}
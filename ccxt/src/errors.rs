@@ -1,17 +1,78 @@
+use std::fmt;
+
 use thiserror::Error;
 
+/// Raw error message returned by the exchange and the endpoint that returned it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiMessage {
+    pub endpoint: &'static str,
+    pub message: String,
+}
+
+impl ApiMessage {
+    pub fn new(endpoint: &'static str, message: &str) -> Self {
+        ApiMessage {
+            endpoint,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ApiMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.endpoint)
+    }
+}
+
+/// Maps exchange error codes onto the error variants.
+pub type ErrorTable = &'static [(&'static str, fn(ApiMessage) -> Error)];
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("API does not support function {0}")]
     ApiFunctionNotSupported(&'static str),
     #[error("error {0} when calling API")]
-    ApiCallError(String),
+    ApiCallError(ApiMessage),
     #[error("no data returned")]
     ApiCallNoData(),
+    #[error("missing argument {0}")]
+    ArgumentsRequired(&'static str),
+    #[error("unsupported api key: {0}")]
+    UnsupportedApiKey(&'static str),
+    #[error("unknown exchange {0}")]
+    UnknownExchange(String),
+    #[error("unknown symbol {0}")]
+    BadSymbol(String),
+    #[error("withdrawal key {0} is not in the allowed list")]
+    WithdrawalKeyNotAllowed(String),
     #[error("Account Balance has no positions")]
     AccountBalanceEmpty(),
+    #[error("authentication failed: {0}")]
+    AuthenticationError(ApiMessage),
+    #[error("permission denied: {0}")]
+    PermissionDenied(ApiMessage),
+    #[error("insufficient funds: {0}")]
+    InsufficientFunds(ApiMessage),
+    #[error("invalid order: {0}")]
+    InvalidOrder(ApiMessage),
+    #[error("order not found: {0}")]
+    OrderNotFound(ApiMessage),
+    #[error("rate limit exceeded: {0}")]
+    RateLimitExceeded(ApiMessage),
+    #[error("invalid nonce: {0}")]
+    InvalidNonce(ApiMessage),
+    #[error("exchange not available: {0}")]
+    ExchangeNotAvailable(ApiMessage),
+    #[error("exchange on maintenance: {0}")]
+    OnMaintenance(ApiMessage),
+    #[error("request blocked by DDoS protection: {0}")]
+    DDoSProtection(ApiMessage),
     #[error("http error {0}")]
     Http(#[from] super::reqwest::Error),
+    #[error("websocket error {0}")]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+    #[error("stream {0} lagged behind, updates were dropped")]
+    Lagged(String),
     #[error(transparent)]
     Serde(#[from] super::serde_json::Error),
     #[error("general exchange error")]
@@ -20,3 +81,36 @@ pub enum Error {
     Unknown,
 }
 
+impl Error {
+    /// Error of the first table entry `code` starts with, `ApiCallError` carrying the same message when there is none.
+    pub fn from_table(table: ErrorTable, code: &str, endpoint: &'static str, message: &str) -> Error {
+        match table.iter().find(|(c, _)| code.starts_with(c)) {
+            Some((_, error)) => error(ApiMessage::new(endpoint, message)),
+            None => Error::ApiCallError(ApiMessage::new(endpoint, message)),
+        }
+    }
+
+    /// Raw message and endpoint of errors reported by the exchange.
+    pub fn api_message(&self) -> Option<&ApiMessage> {
+        match self {
+            Error::ApiCallError(m)
+            | Error::AuthenticationError(m)
+            | Error::PermissionDenied(m)
+            | Error::InsufficientFunds(m)
+            | Error::InvalidOrder(m)
+            | Error::OrderNotFound(m)
+            | Error::RateLimitExceeded(m)
+            | Error::InvalidNonce(m)
+            | Error::ExchangeNotAvailable(m)
+            | Error::OnMaintenance(m)
+            | Error::DDoSProtection(m) => Some(m),
+            _ => None,
+        }
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(e))
+    }
+}
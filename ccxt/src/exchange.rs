@@ -1,7 +1,7 @@
-use std::{collections::{BTreeMap, HashMap}};
+use std::{collections::{BTreeMap, HashMap}, sync::RwLock};
 use async_trait::async_trait;
 
-use crate::{ApiRequest, DateTime, Result, errors::Error};
+use crate::{ApiRequest, DateTime, Decimal, Result, errors::Error};
 
 #[derive(Debug,Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AccessType {
@@ -18,7 +18,7 @@ pub enum Action {
 
 pub type UrlPath = &'static str;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, Default)]
 #[serde(rename(deserialize = ""), rename_all = "camelCase")]
 pub struct ApiConfig {
     pub key: String,
@@ -66,27 +66,128 @@ impl Api {
         }
     }
 
+    /// Replaces `{name}` placeholders in the path with the payload values of the same name.
+    /// The substituted values are removed from the payload.
+    pub fn expand_uri_path(uri_path: &str, payload: &mut HashMap<&str, String>) -> String {
+        let mut path = uri_path.to_string();
+        payload.retain(|name, value| {
+            let placeholder = format!("{{{}}}", name);
+            if path.contains(&placeholder) {
+                path = path.replace(&placeholder, value);
+                false
+            } else {
+                true
+            }
+        });
+        path
+    }
+
     pub fn encode_uri(values: &HashMap<&str, String>) -> String {
         if values.is_empty() {
             return "".to_string();
         }
         let mut acc = "".to_string();
         for (name, param) in values {
-            acc += &(name.to_string() + "=" + param + "&");
+            acc += &(Api::encode_component(name) + "=" + &Api::encode_component(param) + "&");
         }
         acc.pop(); // remove the last "&"
         acc
     }
+
+    /// Support of every unified method, derived from the registered functions.
+    pub fn has(&self) -> BTreeMap<&'static str, Capability> {
+        UNIFIED_METHODS.iter()
+            .map(|(method, ways)| {
+                let capability = ways.iter()
+                    .find(|(_, required)| required.iter().all(|f| self.functions.contains_key(f)))
+                    .map_or(Capability::Absent, |(c, _)| *c);
+                (*method, capability)
+            })
+            .collect()
+    }
+
+    /// application/x-www-form-urlencoded encoding of a single name or value
+    pub fn encode_component(value: &str) -> String {
+        let mut acc = String::with_capacity(value.len());
+        for b in value.bytes() {
+            match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'*' | b'-' | b'.' | b'_' => acc.push(b as char),
+                b' ' => acc.push('+'),
+                _ => acc += &format!("%{:02X}", b),
+            }
+        }
+        acc
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Capability {
+    /// backed by a dedicated endpoint
+    Supported,
+    /// built from other endpoints, e.g. one request per symbol
+    Emulated,
+    Absent,
 }
 
+/// Functions that together implement a unified method.
+type Implementation = (Capability, &'static [Functionality]);
+
+/// Unified methods with the functions they need, first match wins.
+const UNIFIED_METHODS: &[(&str, &[Implementation])] = &[
+    ("cancel_all_orders", &[(Capability::Emulated, &[Functionality::OpenOrders, Functionality::CancelOrder])]),
+    ("cancel_order", &[(Capability::Supported, &[Functionality::CancelOrder])]),
+    ("cancel_withdrawal", &[(Capability::Supported, &[Functionality::WithdrawCancel])]),
+    ("create_order", &[(Capability::Supported, &[Functionality::AddOrder])]),
+    ("fetch_closed_orders", &[(Capability::Supported, &[Functionality::ClosedOrders])]),
+    ("fetch_deposit_address", &[(Capability::Supported, &[Functionality::DepositAddresses])]),
+    ("fetch_deposit_methods", &[(Capability::Supported, &[Functionality::DepositMethods])]),
+    ("fetch_deposits", &[(Capability::Supported, &[Functionality::DepositStatus])]),
+    ("fetch_ledger", &[(Capability::Supported, &[Functionality::Ledgers])]),
+    ("fetch_ledger_entries_by_ids", &[(Capability::Supported, &[Functionality::QueryLedgers])]),
+    ("fetch_markets", &[
+        (Capability::Supported, &[Functionality::AssetPairs]),
+        (Capability::Emulated, &[Functionality::Assets, Functionality::CryptoAssets]),
+    ]),
+    ("fetch_my_trades", &[(Capability::Supported, &[Functionality::TradesHistory])]),
+    ("fetch_my_trades_by_ids", &[(Capability::Supported, &[Functionality::QueryTrades])]),
+    ("fetch_ohlcv", &[(Capability::Supported, &[Functionality::OHLC])]),
+    ("fetch_open_orders", &[(Capability::Supported, &[Functionality::OpenOrders])]),
+    ("fetch_order", &[(Capability::Supported, &[Functionality::QueryOrders])]),
+    ("fetch_order_book", &[(Capability::Supported, &[Functionality::Depth])]),
+    ("fetch_orders", &[(Capability::Supported, &[Functionality::QueryOrders])]),
+    ("fetch_positions", &[(Capability::Supported, &[Functionality::OpenPositions])]),
+    ("fetch_ticker", &[
+        (Capability::Supported, &[Functionality::Ticker]),
+        (Capability::Supported, &[Functionality::SpotPrice, Functionality::BuyPrice, Functionality::SellPrice]),
+    ]),
+    ("fetch_tickers", &[
+        (Capability::Supported, &[Functionality::Ticker]),
+        (Capability::Emulated, &[Functionality::SpotPrice, Functionality::BuyPrice, Functionality::SellPrice]),
+    ]),
+    ("fetch_trades", &[(Capability::Supported, &[Functionality::Trades])]),
+    ("fetch_trading_fees", &[(Capability::Supported, &[Functionality::TradeVolume])]),
+    ("fetch_withdraw_info", &[(Capability::Supported, &[Functionality::WithdrawInfo])]),
+    ("fetch_withdrawals", &[(Capability::Supported, &[Functionality::WithdrawStatus])]),
+    ("get_balance", &[
+        (Capability::Supported, &[Functionality::Balance]),
+        (Capability::Supported, &[Functionality::Accounts]),
+    ]),
+    ("get_status", &[(Capability::Supported, &[Functionality::SystemStatus])]),
+    ("get_time", &[(Capability::Supported, &[Functionality::Time])]),
+    ("withdraw", &[(Capability::Supported, &[Functionality::Withdraw])]),
+];
+
 #[derive(Debug,Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Functionality {
+    Accounts,
     AddOrder,
     Assets,
     AssetPairs,
     Balance,
+    BuyPrice,
     CancelOrder,
     ClosedOrders,
+    CryptoAssets,
     DepositAddresses,
     DepositMethods,
     DepositStatus,
@@ -99,6 +200,8 @@ pub enum Functionality {
     QueryLedgers,
     QueryOrders,
     QueryTrades,
+    SellPrice,
+    SpotPrice,
     Spread,
     SystemStatus,
     Ticker,
@@ -118,7 +221,11 @@ pub enum Functionality {
 pub struct FunctionalityParams {
     pub access_type: AccessType,
     pub action: Action,
-    pub uri_path: &'static str
+    pub uri_path: &'static str,
+    /// rate limiter weight of a single call
+    pub cost: u32,
+    /// whether a failed call can be repeated without side effects
+    pub idempotent: bool,
 }
 
 impl FunctionalityParams {
@@ -126,9 +233,22 @@ impl FunctionalityParams {
         FunctionalityParams {
             access_type,
             action,
-            uri_path
+            uri_path,
+            cost: 1,
+            idempotent: true,
         }
     }
+
+    pub fn cost(mut self, value: u32) -> Self {
+        self.cost = value;
+        self
+    }
+
+    /// Marks calls that must not be retried, e.g. placing an order.
+    pub fn non_idempotent(mut self) -> Self {
+        self.idempotent = false;
+        self
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -163,6 +283,8 @@ pub struct Exchange {
     pub pro: bool,
     pub user_agent: Option<(UserAgent,&'static str)>,
     pub headers: BTreeMap<&'static str, &'static str>,
+    /// exchange specific currency id => unified currency code
+    pub common_currencies: BTreeMap<&'static str, &'static str>,
 }
 
 impl Exchange {
@@ -206,7 +328,47 @@ impl Exchange {
         self.headers.insert(key, value);
         self
     }
+
+    pub fn common_currency(mut self, id: &'static str, code: &'static str) -> Self {
+        self.common_currencies.insert(id, code);
+        self
+    }
+
+    pub fn common_currency_code(&self, id: &str) -> String {
+        match self.common_currencies.get(id) {
+            Some(code) => code.to_string(),
+            None => id.to_string()
+        }
+    }
+
+    pub fn currency_id(&self, code: &str) -> String {
+        match self.common_currencies.iter().find(|(_, c)| **c == code) {
+            Some((id, _)) => id.to_string(),
+            None => code.to_string()
+        }
+    }
+}
+
+/// Splits an unified symbol like BTC/USD into base and quote codes.
+pub fn split_symbol(symbol: &str) -> Result<(&str, &str)> {
+    match symbol.split_once('/') {
+        Some((base, quote)) if !base.is_empty() && !quote.is_empty() => Ok((base, quote)),
+        _ => Err(Error::BadSymbol(symbol.to_string()))
+    }
 }
+
+/// Cuts items sorted in ascending order down to `limit`:
+/// the first ones at or after `since` when it is given, otherwise the most recent ones.
+pub fn limit_items<T>(items: &mut Vec<T>, since: Option<DateTime>, limit: Option<usize>) {
+    if let Some(l) = limit {
+        if since.is_some() {
+            items.truncate(l);
+        } else if items.len() > l {
+            items.drain(..items.len() - l);
+        }
+    }
+}
+
 pub trait ApiCalls {
     fn get_url(&self, params: &FunctionalityParams) -> String;
     fn get_uri_path(&self, params: &FunctionalityParams) -> String;
@@ -226,11 +388,746 @@ pub trait ServerTime {
 pub trait SystemStatus {
     async fn get_status(&self) -> Result<String>;
 }
-#[derive(Deserialize, Debug, Clone, Default)]
+/// Amounts held in a single currency; `total` is always `free + used`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CurrencyBalance {
+    pub free: Decimal,
+    pub used: Decimal,
+    pub total: Decimal,
+}
+
+impl CurrencyBalance {
+    pub fn new(total: Decimal, used: Decimal) -> Self {
+        CurrencyBalance {
+            free: total - used,
+            used,
+            total
+        }
+    }
+}
+
+/// Balances keyed by unified currency code.
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct AccountBalance {
-    pub CHF: Option<String>
+    pub balances: BTreeMap<String, CurrencyBalance>,
+}
+
+impl AccountBalance {
+    pub fn get(&self, code: &str) -> Option<&CurrencyBalance> {
+        self.balances.get(code)
+    }
+
+    /// Adds the amounts to the currency, summing entries that map to the same code.
+    pub fn add(&mut self, code: String, total: Decimal, used: Decimal) {
+        let entry = self.balances.entry(code).or_default();
+        *entry = CurrencyBalance::new(entry.total + total, entry.used + used);
+    }
 }
 #[async_trait]
 pub trait Balance {
     async fn get_balance(&self) -> Result<AccountBalance>;
 }
+
+/// Number of decimal places accepted by the exchange for a market.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MarketPrecision {
+    pub price: Option<u32>,
+    pub amount: Option<u32>,
+    pub cost: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MinMax {
+    pub min: Option<Decimal>,
+    pub max: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MarketLimits {
+    pub amount: MinMax,
+    pub price: MinMax,
+    pub cost: MinMax,
+    pub leverage: MinMax,
+}
+
+/// Fee applied once the 30 day volume reaches `volume`; `fee` is a fraction (0.0026 == 0.26%).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeTier {
+    pub volume: Decimal,
+    pub fee: Decimal,
+}
+
+/// Currency a trading fee is charged in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FeeSide {
+    #[default]
+    Quote,
+    Base,
+    /// the currency received by the trade
+    Get,
+    /// the currency given by the trade
+    Give,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Market {
+    /// exchange specific id, e.g. XXBTZUSD
+    pub id: String,
+    /// other names the exchange uses for the market, e.g. XBTUSD and XBT/USD
+    pub alt_ids: Vec<String>,
+    /// unified symbol, e.g. BTC/USD
+    pub symbol: String,
+    pub base: String,
+    pub quote: String,
+    pub base_id: String,
+    pub quote_id: String,
+    /// None when the exchange does not report whether the market trades
+    pub active: Option<bool>,
+    pub precision: MarketPrecision,
+    pub limits: MarketLimits,
+    pub taker: Option<Decimal>,
+    pub maker: Option<Decimal>,
+    pub taker_tiers: Vec<FeeTier>,
+    pub maker_tiers: Vec<FeeTier>,
+    pub fee_side: FeeSide,
+}
+
+/// Markets loaded from the exchange, indexed by unified symbol and by exchange id or alternative id.
+#[derive(Debug, Clone, Default)]
+pub struct MarketCache {
+    by_symbol: BTreeMap<String, Market>,
+    by_id: HashMap<String, String>,
+}
+
+impl MarketCache {
+    pub fn new(markets: Vec<Market>) -> Self {
+        let mut cache = MarketCache::default();
+        for m in markets {
+            cache.by_id.insert(m.id.clone(), m.symbol.clone());
+            for alt_id in &m.alt_ids {
+                cache.by_id.entry(alt_id.clone()).or_insert_with(|| m.symbol.clone());
+            }
+            cache.by_symbol.insert(m.symbol.clone(), m);
+        }
+        cache
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_symbol.is_empty()
+    }
+
+    pub fn markets(&self) -> &BTreeMap<String, Market> {
+        &self.by_symbol
+    }
+
+    pub fn market(&self, symbol: &str) -> Option<&Market> {
+        self.by_symbol.get(symbol)
+    }
+
+    pub fn market_by_id(&self, id: &str) -> Option<&Market> {
+        self.by_id.get(id).and_then(|s| self.by_symbol.get(s))
+    }
+
+    pub fn market_mut(&mut self, symbol: &str) -> Option<&mut Market> {
+        self.by_symbol.get_mut(symbol)
+    }
+}
+
+impl Market {
+    /// Truncates the amount to the market precision so it never exceeds the requested amount.
+    pub fn amount_to_precision(&self, amount: Decimal) -> Decimal {
+        match self.precision.amount {
+            Some(dp) => amount.trunc_with_scale(dp).normalize(),
+            None => amount
+        }
+    }
+
+    pub fn price_to_precision(&self, price: Decimal) -> Decimal {
+        match self.precision.price {
+            Some(dp) => price.round_dp(dp).normalize(),
+            None => price
+        }
+    }
+}
+
+#[async_trait]
+pub trait Markets: Sync {
+    async fn fetch_markets(&self) -> Result<Vec<Market>>;
+
+    fn market_cache(&self) -> &RwLock<MarketCache>;
+
+    /// Fetches the markets on first use and serves them from the cache afterwards.
+    async fn load_markets(&self, reload: bool) -> Result<BTreeMap<String, Market>> {
+        {
+            let cache = self.market_cache().read().unwrap();
+            if !reload && !cache.is_empty() {
+                return Ok(cache.markets().clone());
+            }
+        }
+        let markets = self.fetch_markets().await?;
+        let mut cache = self.market_cache().write().unwrap();
+        *cache = MarketCache::new(markets);
+        Ok(cache.markets().clone())
+    }
+
+    async fn market(&self, symbol: &str) -> Result<Market> {
+        self.load_markets(false).await?;
+        let cache = self.market_cache().read().unwrap();
+        cache.market(symbol)
+            .cloned()
+            .ok_or_else(|| Error::BadSymbol(symbol.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ticker {
+    pub symbol: String,
+    pub timestamp: Option<DateTime>,
+    pub bid: Option<Decimal>,
+    pub ask: Option<Decimal>,
+    pub last: Option<Decimal>,
+    pub high: Option<Decimal>,
+    pub low: Option<Decimal>,
+    pub vwap: Option<Decimal>,
+    pub open: Option<Decimal>,
+    /// last - open
+    pub change: Option<Decimal>,
+    /// change in percent of open
+    pub percentage: Option<Decimal>,
+    pub base_volume: Option<Decimal>,
+    pub quote_volume: Option<Decimal>,
+}
+
+impl Ticker {
+    /// Fills change and percentage from open and last.
+    pub fn with_change(mut self) -> Self {
+        if let (Some(open), Some(last)) = (self.open, self.last) {
+            self.change = Some(last - open);
+            if !open.is_zero() {
+                self.percentage = Some((last - open) / open * Decimal::ONE_HUNDRED);
+            }
+        }
+        self
+    }
+}
+
+#[async_trait]
+pub trait Tickers {
+    async fn fetch_ticker(&self, symbol: &str) -> Result<Ticker>;
+    /// Tickers keyed by unified symbol; an empty `symbols` slice requests every market.
+    async fn fetch_tickers(&self, symbols: &[&str]) -> Result<BTreeMap<String, Ticker>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderBookLevel {
+    pub price: Decimal,
+    pub amount: Decimal,
+}
+
+/// Level 2 snapshot; bids are sorted best (highest) first, asks best (lowest) first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderBook {
+    pub symbol: String,
+    pub bids: Vec<OrderBookLevel>,
+    pub asks: Vec<OrderBookLevel>,
+    pub timestamp: Option<DateTime>,
+    pub nonce: Option<u64>,
+}
+
+impl OrderBook {
+    pub fn sort(&mut self) {
+        self.bids.sort_by_key(|l| std::cmp::Reverse(l.price));
+        self.asks.sort_by_key(|l| l.price);
+    }
+
+    pub fn best_bid(&self) -> Option<&OrderBookLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&OrderBookLevel> {
+        self.asks.first()
+    }
+}
+
+#[async_trait]
+pub trait OrderBooks {
+    /// `limit` caps the number of levels returned on each side.
+    async fn fetch_order_book(&self, symbol: &str, limit: Option<u32>) -> Result<OrderBook>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Timeframe {
+    Minute1,
+    Minute5,
+    Minute15,
+    Minute30,
+    Hour1,
+    Hour4,
+    Day1,
+    Week1,
+    Day15,
+}
+
+impl Timeframe {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Timeframe::Minute1 => "1m",
+            Timeframe::Minute5 => "5m",
+            Timeframe::Minute15 => "15m",
+            Timeframe::Minute30 => "30m",
+            Timeframe::Hour1 => "1h",
+            Timeframe::Hour4 => "4h",
+            Timeframe::Day1 => "1d",
+            Timeframe::Week1 => "1w",
+            Timeframe::Day15 => "15d",
+        }
+    }
+
+    pub fn minutes(&self) -> u32 {
+        match self {
+            Timeframe::Minute1 => 1,
+            Timeframe::Minute5 => 5,
+            Timeframe::Minute15 => 15,
+            Timeframe::Minute30 => 30,
+            Timeframe::Hour1 => 60,
+            Timeframe::Hour4 => 240,
+            Timeframe::Day1 => 1440,
+            Timeframe::Week1 => 10080,
+            Timeframe::Day15 => 21600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candle {
+    /// start of the candle
+    pub timestamp: DateTime,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+}
+
+#[async_trait]
+pub trait Candles {
+    /// Candles in ascending order. With `since` the candles starting at or after it are returned,
+    /// otherwise the most recent ones; `limit` caps the number of candles.
+    async fn fetch_ohlcv(&self, symbol: &str, timeframe: Timeframe, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Candle>>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OrderType {
+    Market,
+    Limit,
+    /// market order once the stop price is reached
+    StopLoss,
+    /// market order once the take profit price is reached
+    TakeProfit,
+    /// limit order once the stop price is reached
+    StopLossLimit,
+    /// limit order once the take profit price is reached
+    TakeProfitLimit,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimeInForce {
+    GoodTillCancel,
+    ImmediateOrCancel,
+    GoodTillDate(DateTime),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OrderStatus {
+    Pending,
+    Open,
+    Closed,
+    Canceled,
+    Expired,
+    Rejected,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TakerOrMaker {
+    Taker,
+    Maker,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub id: Option<String>,
+    pub symbol: String,
+    pub timestamp: DateTime,
+    pub side: Side,
+    pub price: Decimal,
+    pub amount: Decimal,
+    /// price * amount
+    pub cost: Decimal,
+    pub order_type: Option<OrderType>,
+    /// set for own trades only
+    pub order_id: Option<String>,
+    pub fee: Option<Decimal>,
+    pub fee_currency: Option<String>,
+    pub taker_or_maker: Option<TakerOrMaker>,
+}
+
+#[async_trait]
+pub trait Trades {
+    /// Trades in ascending order. With `since` the trades at or after it are returned,
+    /// otherwise the most recent ones; `limit` caps the number of trades.
+    async fn fetch_trades(&self, symbol: &str, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Trade>>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    /// empty when the order was only validated
+    pub id: String,
+    pub client_order_id: Option<String>,
+    pub symbol: String,
+    pub timestamp: Option<DateTime>,
+    pub status: OrderStatus,
+    pub order_type: OrderType,
+    pub side: Side,
+    pub time_in_force: Option<TimeInForce>,
+    pub post_only: bool,
+    pub price: Option<Decimal>,
+    pub stop_price: Option<Decimal>,
+    pub amount: Decimal,
+    pub filled: Decimal,
+    pub remaining: Decimal,
+    pub cost: Decimal,
+    pub average: Option<Decimal>,
+    pub fee: Option<Decimal>,
+    /// human readable description provided by the exchange
+    pub description: Option<String>,
+}
+
+/// Parameters of a new order, created with `OrderRequest::market` or `OrderRequest::limit`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRequest {
+    pub symbol: String,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub amount: Decimal,
+    /// limit price
+    pub price: Option<Decimal>,
+    /// trigger price of stop loss and take profit orders
+    pub stop_price: Option<Decimal>,
+    pub time_in_force: Option<TimeInForce>,
+    pub post_only: bool,
+    pub leverage: Option<Decimal>,
+    pub client_order_id: Option<String>,
+    /// validate the order without placing it
+    pub validate: bool,
+}
+
+impl OrderRequest {
+    pub fn new(symbol: &str, side: Side, order_type: OrderType, amount: Decimal) -> Self {
+        OrderRequest {
+            symbol: symbol.to_string(),
+            side,
+            order_type,
+            amount,
+            price: None,
+            stop_price: None,
+            time_in_force: None,
+            post_only: false,
+            leverage: None,
+            client_order_id: None,
+            validate: false,
+        }
+    }
+
+    pub fn market(symbol: &str, side: Side, amount: Decimal) -> Self {
+        OrderRequest::new(symbol, side, OrderType::Market, amount)
+    }
+
+    pub fn limit(symbol: &str, side: Side, amount: Decimal, price: Decimal) -> Self {
+        OrderRequest::new(symbol, side, OrderType::Limit, amount).price(price)
+    }
+
+    pub fn price(mut self, value: Decimal) -> Self {
+        self.price = Some(value);
+        self
+    }
+
+    pub fn stop_price(mut self, value: Decimal) -> Self {
+        self.stop_price = Some(value);
+        self
+    }
+
+    pub fn time_in_force(mut self, value: TimeInForce) -> Self {
+        self.time_in_force = Some(value);
+        self
+    }
+
+    pub fn post_only(mut self, value: bool) -> Self {
+        self.post_only = value;
+        self
+    }
+
+    pub fn leverage(mut self, value: Decimal) -> Self {
+        self.leverage = Some(value);
+        self
+    }
+
+    pub fn client_order_id(mut self, value: String) -> Self {
+        self.client_order_id = Some(value);
+        self
+    }
+
+    pub fn validate(mut self, value: bool) -> Self {
+        self.validate = value;
+        self
+    }
+}
+
+#[async_trait]
+pub trait Trading {
+    async fn create_order(&self, request: &OrderRequest) -> Result<Order>;
+
+    async fn cancel_order(&self, id: &str) -> Result<()>;
+
+    /// Cancels the open orders of `symbol` or of every market; returns the result of every cancel by order id.
+    async fn cancel_all_orders(&self, symbol: Option<&str>) -> Result<BTreeMap<String, Result<()>>>;
+
+    async fn fetch_order(&self, id: &str) -> Result<Order>;
+
+    async fn fetch_orders(&self, ids: &[&str]) -> Result<Vec<Order>>;
+
+    async fn fetch_open_orders(&self, symbol: Option<&str>) -> Result<Vec<Order>>;
+
+    /// Closed orders in ascending order, the most recent ones unless `since` is given.
+    async fn fetch_closed_orders(&self, symbol: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Order>>;
+
+    /// Own trades in ascending order, the most recent ones unless `since` is given.
+    async fn fetch_my_trades(&self, symbol: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Trade>>;
+
+    async fn fetch_my_trades_by_ids(&self, ids: &[&str]) -> Result<Vec<Trade>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LedgerEntryType {
+    Deposit,
+    Withdrawal,
+    Trade,
+    Margin,
+    Rollover,
+    Staking,
+    Transfer,
+    /// exchange specific type without an unified equivalent
+    Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LedgerEntry {
+    pub id: String,
+    /// id of the trade, deposit or withdrawal that caused the entry
+    pub reference_id: Option<String>,
+    pub timestamp: DateTime,
+    pub entry_type: LedgerEntryType,
+    pub currency: String,
+    /// signed change of the balance, fee excluded
+    pub amount: Decimal,
+    pub fee: Decimal,
+    /// balance after the entry
+    pub balance: Option<Decimal>,
+}
+
+#[async_trait]
+pub trait Ledger {
+    /// Ledger entries in ascending order, optionally for a single currency code;
+    /// the most recent ones unless `since` is given.
+    async fn fetch_ledger(&self, code: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<LedgerEntry>>;
+
+    async fn fetch_ledger_entries_by_ids(&self, ids: &[&str]) -> Result<Vec<LedgerEntry>>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepositMethod {
+    pub method: String,
+    /// maximum net amount that can be deposited right now
+    pub limit: Option<Decimal>,
+    pub fee: Option<Decimal>,
+    pub address_setup_fee: Option<Decimal>,
+    pub can_generate_address: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepositAddress {
+    pub currency: String,
+    pub method: String,
+    pub address: String,
+    /// memo / destination tag required by some currencies
+    pub tag: Option<String>,
+    pub expires: Option<DateTime>,
+    /// the address was created by this request
+    pub new: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransactionType {
+    Deposit,
+    Withdrawal,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransactionStatus {
+    Pending,
+    Ok,
+    Failed,
+    Canceled,
+}
+
+/// Deposit or withdrawal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub id: String,
+    /// blockchain transaction id
+    pub txid: Option<String>,
+    pub transaction_type: TransactionType,
+    pub currency: String,
+    pub method: String,
+    pub amount: Decimal,
+    pub fee: Option<Decimal>,
+    pub address: Option<String>,
+    pub status: TransactionStatus,
+    pub timestamp: Option<DateTime>,
+}
+
+/// Where a withdrawal is sent to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WithdrawalDestination {
+    /// name of a destination registered on the exchange beforehand
+    Key(String),
+    Address { address: String, tag: Option<String> },
+}
+
+/// Fee and limit quote for a withdrawal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithdrawalInfo {
+    pub method: String,
+    /// maximum net amount that can be withdrawn right now
+    pub limit: Option<Decimal>,
+    /// net amount that will be sent
+    pub amount: Decimal,
+    pub fee: Decimal,
+}
+
+#[async_trait]
+pub trait Funding {
+    async fn fetch_deposit_methods(&self, code: &str) -> Result<Vec<DepositMethod>>;
+
+    /// Uses the first deposit method of the currency when `method` is not given.
+    async fn fetch_deposit_address(&self, code: &str, method: Option<&str>, create_new: bool) -> Result<DepositAddress>;
+
+    /// Deposits in ascending order, the most recent ones unless `since` is given.
+    async fn fetch_deposits(&self, code: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Transaction>>;
+
+    /// Returns the id of the new withdrawal.
+    async fn withdraw(&self, code: &str, amount: Decimal, destination: &WithdrawalDestination) -> Result<String>;
+
+    async fn fetch_withdraw_info(&self, code: &str, amount: Decimal, destination: &WithdrawalDestination) -> Result<WithdrawalInfo>;
+
+    /// Withdrawals in ascending order, the most recent ones unless `since` is given.
+    async fn fetch_withdrawals(&self, code: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Transaction>>;
+
+    async fn cancel_withdrawal(&self, code: &str, id: &str) -> Result<()>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PositionSide {
+    Long,
+    Short,
+}
+
+/// Open margin position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub id: String,
+    pub symbol: String,
+    pub timestamp: Option<DateTime>,
+    pub side: PositionSide,
+    /// open size in base currency
+    pub size: Decimal,
+    pub entry_price: Option<Decimal>,
+    /// opening cost in quote currency
+    pub cost: Decimal,
+    /// current value in quote currency
+    pub value: Option<Decimal>,
+    pub unrealized_pnl: Option<Decimal>,
+    pub margin: Decimal,
+    pub fee: Decimal,
+    /// next time rollover fees are charged
+    pub rollover_time: Option<DateTime>,
+    /// rollover fee terms as described by the exchange
+    pub rollover_terms: Option<String>,
+}
+
+#[async_trait]
+pub trait Positions {
+    /// Open positions, restricted to `symbols` unless the slice is empty.
+    async fn fetch_positions(&self, symbols: &[&str]) -> Result<Vec<Position>>;
+}
+
+/// Maker/taker rates that apply to the account for a market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradingFee {
+    pub symbol: String,
+    pub maker: Decimal,
+    pub taker: Decimal,
+    /// 30 day trade volume of the account
+    pub volume: Option<Decimal>,
+    pub volume_currency: Option<String>,
+    /// volume at which the current fee tier starts
+    pub tier_volume: Option<Decimal>,
+}
+
+#[async_trait]
+pub trait TradingFees {
+    /// Fees keyed by unified symbol.
+    async fn fetch_trading_fees(&self, symbols: &[&str]) -> Result<BTreeMap<String, TradingFee>>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fee {
+    pub currency: String,
+    pub rate: Decimal,
+    pub cost: Decimal,
+}
+
+/// Estimates the fee of a trade from the market maker/taker rates.
+pub fn calculate_fee(market: &Market, side: Side, amount: Decimal, price: Decimal, taker: bool) -> Result<Fee> {
+    let rate = if taker { market.taker } else { market.maker }
+        .ok_or(Error::ArgumentsRequired("market fee rate"))?;
+    let in_base = match market.fee_side {
+        FeeSide::Quote => false,
+        FeeSide::Base => true,
+        FeeSide::Get => side == Side::Buy,
+        FeeSide::Give => side == Side::Sell,
+    };
+    Ok(if in_base {
+        Fee { currency: market.base.clone(), rate, cost: amount * rate }
+    } else {
+        Fee { currency: market.quote.clone(), rate, cost: amount * price * rate }
+    })
+}
+
+/// Exchange independent interface, usable as `Box<dyn UnifiedExchange>`.
+/// Calls an exchange does not offer fail with `Error::ApiFunctionNotSupported`.
+pub trait UnifiedExchange: ServerTime + SystemStatus + Balance + Markets + Tickers + OrderBooks + Candles + Trades + Trading + Send + Sync {
+    fn exchange(&self) -> &Exchange;
+
+    fn api(&self) -> &Api;
+
+    /// Capability of every unified method, see `Api::has`.
+    fn has(&self) -> BTreeMap<&'static str, Capability> {
+        self.api().has()
+    }
+}
@@ -1,44 +1,299 @@
-use std::{collections::HashMap, net::ToSocketAddrs};
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, sync::{Arc, Mutex, RwLock}, time::Duration};
 
 use async_trait::async_trait;
-use serde::de::DeserializeOwned;
-use crate::{ApiRequest, DateTime, Result, errors::Error, exchange::*};
-use chrono::prelude::Utc;
+use serde::{Deserialize, Deserializer, de::DeserializeOwned};
+use crate::{ApiRequest, DateTime, Decimal, Result, rate_limiter::{DecayingCounter, RateLimiter}, retry::{self, RetryPolicy}, errors::{ApiMessage, Error, ErrorTable}, exchange::*, nonce::{AtomicNonce, NonceSource}};
+use chrono::prelude::{TimeZone, Utc};
+use rust_decimal::prelude::ToPrimitive;
 use sha2::{Digest, Sha256, Sha512};
 use data_encoding::{BASE64};
 use hmac::*;
-use crate::rfc1123_date_format::*;
+use tokio::time::Instant;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Kraken {
     pub exchange: Exchange,
     pub api: Api,
-    pub http_client: reqwest::Client
+    pub http_client: reqwest::Client,
+    /// throttles public calls, private calls are limited by `api_counter`
+    pub rate_limiter: RateLimiter,
+    pub retry_policy: RetryPolicy,
+    nonce: Arc<dyn NonceSource>,
+    markets: Arc<RwLock<MarketCache>>,
+    /// withdrawals are refused for keys outside this list when it is set
+    allowed_withdrawal_keys: Option<BTreeSet<String>>,
+    tier: VerificationTier,
+    api_counter: DecayingCounter,
+    /// matching engine order rate counters by pair id
+    order_counters: Arc<Mutex<HashMap<String, DecayingCounter>>>,
+    /// pair id and placement time of the orders created by this client, used for cancel penalties
+    placed_orders: Arc<Mutex<HashMap<String, (String, Instant)>>>,
 }
 
+/// Account verification tier, it sets the private api and order rate limits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VerificationTier {
+    Starter,
+    Intermediate,
+    Pro,
+}
+
+impl VerificationTier {
+    /// Counter of private api calls.
+    pub fn api_counter(&self) -> DecayingCounter {
+        match self {
+            VerificationTier::Starter => DecayingCounter::new(15, 0.33),
+            VerificationTier::Intermediate => DecayingCounter::new(20, 0.5),
+            VerificationTier::Pro => DecayingCounter::new(20, 1.0),
+        }
+    }
+
+    /// Matching engine counter of placed and canceled orders of a single pair.
+    pub fn order_counter(&self) -> DecayingCounter {
+        match self {
+            VerificationTier::Starter => DecayingCounter::new(60, 1.0),
+            VerificationTier::Intermediate => DecayingCounter::new(125, 2.34),
+            VerificationTier::Pro => DecayingCounter::new(180, 3.75),
+        }
+    }
+}
+
+/// Order rate counter increase for canceling an order of the given age.
+pub fn cancel_penalty(age: Duration) -> u32 {
+    match age.as_secs() {
+        0..=4 => 8,
+        5..=9 => 6,
+        10..=14 => 5,
+        15..=44 => 4,
+        45..=89 => 2,
+        90..=299 => 1,
+        _ => 0,
+    }
+}
+
+/// Kraken error codes, matched as prefixes of the returned messages.
+const ERRORS: ErrorTable = &[
+    ("EAPI:Invalid key", Error::AuthenticationError),
+    ("EAPI:Invalid signature", Error::AuthenticationError),
+    ("EAPI:Invalid nonce", Error::InvalidNonce),
+    ("EAPI:Rate limit exceeded", Error::RateLimitExceeded),
+    ("EAPI:Feature disabled", Error::PermissionDenied),
+    ("EGeneral:Permission denied", Error::PermissionDenied),
+    ("EGeneral:Temporary lockout", Error::DDoSProtection),
+    ("EGeneral:Too many requests", Error::DDoSProtection),
+    ("EGeneral:Internal error", Error::ExchangeNotAvailable),
+    ("EOrder:Rate limit exceeded", Error::RateLimitExceeded),
+    ("EOrder:Insufficient funds", Error::InsufficientFunds),
+    ("EOrder:Insufficient margin", Error::InsufficientFunds),
+    ("EOrder:Margin allowance exceeded", Error::InsufficientFunds),
+    ("EOrder:Unknown order", Error::OrderNotFound),
+    ("EOrder:Invalid order", Error::InvalidOrder),
+    ("EOrder:Invalid price", Error::InvalidOrder),
+    ("EOrder:Order minimum not met", Error::InvalidOrder),
+    ("EOrder:Cost minimum not met", Error::InvalidOrder),
+    ("EOrder:Tick size check failed", Error::InvalidOrder),
+    ("EOrder:Cannot open position", Error::InvalidOrder),
+    ("EOrder:Orders limit exceeded", Error::InvalidOrder),
+    ("EOrder:Positions limit exceeded", Error::InvalidOrder),
+    ("EOrder:Post only order", Error::InvalidOrder),
+    ("EFunding:Insufficient funds", Error::InsufficientFunds),
+    ("EService:Unavailable", Error::ExchangeNotAvailable),
+    ("EService:Busy", Error::ExchangeNotAvailable),
+    ("EService:Deadline elapsed", Error::ExchangeNotAvailable),
+    ("EService:Market in cancel_only mode", Error::OnMaintenance),
+    ("EService:Market in post_only mode", Error::OnMaintenance),
+    ("EService:Market in limit_only mode", Error::OnMaintenance),
+    ("EDatabase:Internal error", Error::ExchangeNotAvailable),
+];
+
+/// Orders older than this are canceled without penalty.
+const CANCEL_PENALTY_AGE: Duration = Duration::from_secs(300);
+
+/// Asset ids Kraken still reports with the legacy X (crypto) / Z (fiat) prefix.
+const LEGACY_ASSET_IDS: &[&str] = &[
+    "XETC", "XETH", "XLTC", "XMLN", "XREP", "XXBT", "XXDG", "XXLM", "XXMR", "XXRP", "XZEC",
+    "ZAUD", "ZCAD", "ZEUR", "ZGBP", "ZJPY", "ZUSD",
+];
+
+
+/// Maximum number of txids accepted by QueryOrders.
+const MAX_QUERY_ORDERS: usize = 50;
+
+/// Maximum number of ids accepted by QueryTrades and QueryLedgers.
+const MAX_QUERY_IDS: usize = 20;
+
+/// Quote currencies used to split pair ids that are not in the market cache.
+const QUOTE_IDS: &[&str] = &[
+    "USDT", "USDC", "USD", "EUR", "GBP", "CAD", "JPY", "CHF", "AUD", "DAI", "XBT", "ETH", "DOT",
+];
+
 impl Kraken {
     pub fn new(id: &'static str, api_key: String, api_secret: String) -> Self {
-        Kraken {
-            exchange: Exchange::new(id, "Kraken")
+        let exchange = Exchange::new(id, "Kraken")
             .rate_limit(3000)
-            .countries(Country::UnitedStates),
+            .countries(Country::UnitedStates)
+            .common_currency("XBT", "BTC")
+            .common_currency("XDG", "DOGE");
+        Kraken {
+            rate_limiter: RateLimiter::from_millis(exchange.rate_limit),
+            exchange,
             api: Api::new("https://api.kraken.com", "0")
             .api_key(api_key)
             .api_secret(api_secret)
-            .function(Functionality::Balance,FunctionalityParams::new(AccessType::Private, Action::Post, "Balance"))
+            .function(Functionality::AddOrder,FunctionalityParams::new(AccessType::Private, Action::Post, "AddOrder").cost(0).non_idempotent())
+            .function(Functionality::AssetPairs,FunctionalityParams::new(AccessType::Public, Action::Get, "AssetPairs"))
+            .function(Functionality::Balance,FunctionalityParams::new(AccessType::Private, Action::Post, "BalanceEx"))
+            .function(Functionality::CancelOrder,FunctionalityParams::new(AccessType::Private, Action::Post, "CancelOrder").cost(0))
+            .function(Functionality::ClosedOrders,FunctionalityParams::new(AccessType::Private, Action::Post, "ClosedOrders"))
+            .function(Functionality::DepositAddresses,FunctionalityParams::new(AccessType::Private, Action::Post, "DepositAddresses"))
+            .function(Functionality::DepositMethods,FunctionalityParams::new(AccessType::Private, Action::Post, "DepositMethods"))
+            .function(Functionality::DepositStatus,FunctionalityParams::new(AccessType::Private, Action::Post, "DepositStatus"))
+            .function(Functionality::Depth,FunctionalityParams::new(AccessType::Public, Action::Get, "Depth"))
             .function(Functionality::GetWebSocketsToken,FunctionalityParams::new(AccessType::Private, Action::Get, "GetWebSocketsToken"))
+            .function(Functionality::Ledgers,FunctionalityParams::new(AccessType::Private, Action::Post, "Ledgers").cost(2))
+            .function(Functionality::OpenOrders,FunctionalityParams::new(AccessType::Private, Action::Post, "OpenOrders"))
+            .function(Functionality::OpenPositions,FunctionalityParams::new(AccessType::Private, Action::Post, "OpenPositions"))
+            .function(Functionality::QueryLedgers,FunctionalityParams::new(AccessType::Private, Action::Post, "QueryLedgers").cost(2))
+            .function(Functionality::QueryOrders,FunctionalityParams::new(AccessType::Private, Action::Post, "QueryOrders"))
+            .function(Functionality::QueryTrades,FunctionalityParams::new(AccessType::Private, Action::Post, "QueryTrades").cost(2))
+            .function(Functionality::TradeVolume,FunctionalityParams::new(AccessType::Private, Action::Post, "TradeVolume"))
+            .function(Functionality::TradeBalance,FunctionalityParams::new(AccessType::Private, Action::Post, "TradeBalance"))
+            .function(Functionality::Spread,FunctionalityParams::new(AccessType::Public, Action::Get, "Spread"))
             .function(Functionality::SystemStatus,FunctionalityParams::new(AccessType::Public, Action::Get, "SystemStatus"))
+            .function(Functionality::OHLC,FunctionalityParams::new(AccessType::Public, Action::Get, "OHLC"))
+            .function(Functionality::Ticker,FunctionalityParams::new(AccessType::Public, Action::Get, "Ticker"))
             .function(Functionality::Time,FunctionalityParams::new(AccessType::Public, Action::Get, "Time"))
+            .function(Functionality::Withdraw,FunctionalityParams::new(AccessType::Private, Action::Post, "Withdraw").non_idempotent())
+            .function(Functionality::WithdrawCancel,FunctionalityParams::new(AccessType::Private, Action::Post, "WithdrawCancel"))
+            .function(Functionality::WithdrawInfo,FunctionalityParams::new(AccessType::Private, Action::Post, "WithdrawInfo"))
+            .function(Functionality::WithdrawStatus,FunctionalityParams::new(AccessType::Private, Action::Post, "WithdrawStatus"))
+            .function(Functionality::Trades,FunctionalityParams::new(AccessType::Public, Action::Get, "Trades"))
+            .function(Functionality::TradesHistory,FunctionalityParams::new(AccessType::Private, Action::Post, "TradesHistory").cost(2))
             ,
-            http_client: reqwest::Client::new()    
+            http_client: reqwest::Client::new(),
+            markets: Arc::new(RwLock::new(MarketCache::default())),
+            allowed_withdrawal_keys: None,
+            tier: VerificationTier::Starter,
+            api_counter: VerificationTier::Starter.api_counter(),
+            order_counters: Arc::new(Mutex::new(HashMap::new())),
+            placed_orders: Arc::new(Mutex::new(HashMap::new())),
+            retry_policy: RetryPolicy::default(),
+            nonce: Arc::new(AtomicNonce::new()),
             }
     }
 
+    /// Use a shared nonce source, e.g. `FileNonce` when several processes use the same API key.
+    pub fn nonce_source(mut self, nonce: Arc<dyn NonceSource>) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Sets the verification tier of the account, the lowest tier is assumed by default.
+    pub fn tier(mut self, tier: VerificationTier) -> Self {
+        self.tier = tier;
+        self.api_counter = tier.api_counter();
+        self.order_counters = Arc::new(Mutex::new(HashMap::new()));
+        self
+    }
+
+    fn order_counter(&self, pair: &str) -> DecayingCounter {
+        self.order_counters.lock().unwrap()
+            .entry(pair.to_string())
+            .or_insert_with(|| self.tier.order_counter())
+            .clone()
+    }
+
+    fn track_order(&self, id: &str, pair: &str) {
+        let mut placed = self.placed_orders.lock().unwrap();
+        placed.retain(|_, (_, at)| at.elapsed() < CANCEL_PENALTY_AGE);
+        placed.insert(id.to_string(), (pair.to_string(), Instant::now()));
+    }
+
+    /// Only allow withdrawals to these registered withdrawal key names.
+    pub fn allowed_withdrawal_keys(mut self, keys: &[&str]) -> Self {
+        self.allowed_withdrawal_keys = Some(keys.iter().map(|k| k.to_string()).collect());
+        self
+    }
+
+    /// Kraken only withdraws to key names registered in the account settings.
+    pub fn withdrawal_key<'a>(&self, destination: &'a WithdrawalDestination) -> Result<&'a str> {
+        let key = match destination {
+            WithdrawalDestination::Key(k) => k,
+            WithdrawalDestination::Address { .. } => return Err(Error::ApiFunctionNotSupported("withdrawal to an address, use a withdrawal key"))
+        };
+        match &self.allowed_withdrawal_keys {
+            Some(allowed) if !allowed.contains(key) => Err(Error::WithdrawalKeyNotAllowed(key.clone())),
+            _ => Ok(key)
+        }
+    }
+
+    /// Translates a Kraken asset id (XXBT, ZUSD, XBT, DOT.S) into an unified currency code (BTC, USD, BTC, DOT.S).
+    pub fn currency_code(&self, id: &str) -> String {
+        let (asset, suffix) = match id.split_once('.') {
+            Some((a, s)) => (a, Some(s)),
+            None => (id, None)
+        };
+        let asset = if LEGACY_ASSET_IDS.contains(&asset) { &asset[1..] } else { asset };
+        let code = self.exchange.common_currency_code(asset);
+        match suffix {
+            Some(s) => format!("{}.{}", code, s),
+            None => code
+        }
+    }
+
+    /// Translates an unified currency code into the asset name accepted by Kraken requests.
+    pub fn currency_id(&self, code: &str) -> String {
+        match code.split_once('.') {
+            Some((c, s)) => format!("{}.{}", self.exchange.currency_id(c), s),
+            None => self.exchange.currency_id(code)
+        }
+    }
+
+    /// Translates an unified symbol (BTC/USD) into the Kraken pair id (XXBTZUSD).
+    /// Without loaded markets the pair altname (XBTUSD) is used, which Kraken accepts as well.
+    pub fn market_id(&self, symbol: &str) -> Result<String> {
+        if let Some(m) = self.markets.read().unwrap().market(symbol) {
+            return Ok(m.id.clone());
+        }
+        let (base, quote) = split_symbol(symbol)?;
+        Ok(format!("{}{}", self.currency_id(base), self.currency_id(quote)))
+    }
+
+    /// Translates a Kraken pair id (XXBTZUSD, XBTUSD) into an unified symbol (BTC/USD).
+    pub fn symbol(&self, market_id: &str) -> String {
+        if let Some(m) = self.markets.read().unwrap().market_by_id(market_id) {
+            return m.symbol.clone();
+        }
+        if market_id.len() == 8 {
+            let (base, quote) = market_id.split_at(4);
+            if LEGACY_ASSET_IDS.contains(&base) && LEGACY_ASSET_IDS.contains(&quote) {
+                return format!("{}/{}", self.currency_code(base), self.currency_code(quote));
+            }
+        }
+        match QUOTE_IDS.iter().find(|q| market_id.len() > q.len() && market_id.ends_with(*q)) {
+            Some(quote) => {
+                let base = &market_id[..market_id.len() - quote.len()];
+                format!("{}/{}", self.currency_code(base), self.currency_code(quote))
+            },
+            None => market_id.to_string()
+        }
+    }
+
+    /// Maps a Kraken error message onto the matching error variant.
+    pub fn parse_error(&self, endpoint: &'static str, message: &str) -> Error {
+        Error::from_table(ERRORS, message, endpoint, message)
+    }
+
     pub fn get_signature(&self, uri_path: &String, post_data: &String, nonce: &String) -> String {
         let message_presha256 = format!("{}{}", nonce, post_data);
 
         let mut sha256 = Sha256::default();
-        sha256.update(&message_presha256.as_bytes());
+        sha256.update(message_presha256.as_bytes());
 
         let output = sha256.finalize();
 
@@ -60,15 +315,49 @@ impl Kraken {
         self.get_data::<T>(f, HashMap::<&str, String>::new()).await
     }
 
+    /// Calls retry transient failures, non idempotent ones only when a client order id is given.
     pub async fn get_data<T>(&self, f: &Functionality, payload: HashMap<&str, String>) -> Result<T> 
     where T: DeserializeOwned
     {
-        let rb   = self.get_request(f, payload)?;
-        let r = rb.send().await?;
+        let fp = self.api.get_function_params(f)?;
+        if !fp.idempotent && !payload.contains_key("cl_ord_id") {
+            return self.send_data(f, payload).await;
+        }
+        self.retry_policy.retry(retry::is_transient, || self.send_data(f, payload.clone())).await
+    }
+
+    /// A rejected nonce leaves no side effects, the call is repeated once after resyncing
+    /// the nonce with the Kraken server time. This only recovers from a nonce source that fell behind
+    /// the server clock: when another process with the same API key already sent a nonce ahead of the
+    /// server time the repeated call fails as well, such processes have to share a `FileNonce`.
+    async fn send_data<T>(&self, f: &Functionality, payload: HashMap<&str, String>) -> Result<T> 
+    where T: DeserializeOwned
+    {
+        match self.send_once(f, payload.clone()).await {
+            Err(Error::InvalidNonce(_)) => {
+                let time = self.send_once::<Time>(&Functionality::Time, HashMap::new()).await?;
+                self.nonce.resync(time.unixtime * 1000)?;
+                self.send_once(f, payload).await
+            },
+            result => result,
+        }
+    }
+
+    async fn send_once<T>(&self, f: &Functionality, payload: HashMap<&str, String>) -> Result<T> 
+    where T: DeserializeOwned
+    {
+        // throttle before signing, a request waiting with an older nonce would be rejected
+        let fp = self.api.get_function_params(f)?;
+        match fp.access_type {
+            AccessType::Public => self.rate_limiter.acquire(fp.cost).await,
+            AccessType::Private => self.api_counter.acquire(fp.cost).await,
+        }
+        let rb = self.get_request(f, payload)?;
+        let r = retry::error_for_transient_status(rb.send().await?)?;
 
         let res = r.json::<Data<T>>().await?;
-        if res.error.len() > 0 {
-            return Err(Error::ApiCallError(res.error[0].clone()));
+        if !res.error.is_empty() {
+            return Err(self.parse_error(fp.uri_path, &res.error[0]));
         }
         match res.result {
             None => Err(Error::ApiCallNoData()),
@@ -99,16 +388,15 @@ impl ApiCalls for Kraken {
 
         match fp.access_type {
             AccessType::Public => {
-                return Ok(
+                Ok(
                     self.http_client.get(self.get_url(fp))
-                        .header("Content-Type", "application/x-www-form-urlencoded; charset=utf-8".to_string())
-                        .form(&payload));
+                        .query(&payload))
             },
             AccessType::Private => {
-                let uri_path = self.get_uri_path(&fp);
+                let uri_path = self.get_uri_path(fp);
         
-                let nonce = Utc::now().timestamp_millis().to_string();
-                
+                let nonce = self.nonce.next()?.to_string();
+
                 let mut params = HashMap::new();
                 params.insert("nonce", nonce.clone());
                 params.extend(payload);
@@ -117,11 +405,12 @@ impl ApiCalls for Kraken {
                 
                 let signature = self.get_signature(&uri_path, &post_data, &nonce);
         
+                // send exactly the signed post data
                 let req = self.http_client.post(format!("{}{}", self.api.url, uri_path))
                     .header("API-Key", self.api.key.as_ref().unwrap())
                     .header("API-Sign", &signature)
                     .header("Content-Type", "application/x-www-form-urlencoded; charset=utf-8".to_string())
-                    .form(&params);
+                    .body(post_data);
         
                 Ok(req)
             }
@@ -130,21 +419,500 @@ impl ApiCalls for Kraken {
 }
 
 impl Kraken {
-    
-    pub async fn get_web_socket_token(&self) -> Result<WebSocketToken> {
-        let t = 3;
-        if t {
-            println!("");
+
+    pub fn parse_markets(&self, pairs: BTreeMap<String, AssetPair>) -> Vec<Market> {
+        pairs.iter()
+            // darkpool pairs are not tradeable through the public order book
+            .filter(|(id, _)| !id.ends_with(".d"))
+            .map(|(id, pair)| self.parse_market(id, pair))
+            .collect()
+    }
+
+    pub fn parse_market(&self, id: &str, pair: &AssetPair) -> Market {
+        let (base, quote) = match pair.wsname.as_ref().and_then(|w| w.split_once('/')) {
+            Some((b, q)) => (self.currency_code(b), self.currency_code(q)),
+            None => (self.currency_code(&pair.base), self.currency_code(&pair.quote))
+        };
+        let tiers = |fees: &Vec<(Decimal, Decimal)>| -> Vec<FeeTier> {
+            fees.iter()
+                .map(|(volume, percent)| FeeTier { volume: *volume, fee: percent / Decimal::ONE_HUNDRED })
+                .collect()
+        };
+        let taker_tiers = tiers(&pair.fees);
+        let maker_tiers = tiers(&pair.fees_maker);
+        let max_leverage = pair.leverage_buy.iter().chain(pair.leverage_sell.iter()).max();
+
+        Market {
+            id: id.to_string(),
+            alt_ids: Some(pair.altname.clone()).into_iter().chain(pair.wsname.clone())
+                .filter(|a| !a.is_empty() && a != id)
+                .collect(),
+            symbol: format!("{}/{}", base, quote),
+            base,
+            quote,
+            base_id: pair.base.clone(),
+            quote_id: pair.quote.clone(),
+            active: Some(pair.status.as_ref().is_none_or(|s| s == "online")),
+            precision: MarketPrecision {
+                price: pair.pair_decimals,
+                amount: pair.lot_decimals,
+                cost: pair.cost_decimals,
+            },
+            limits: MarketLimits {
+                amount: MinMax { min: pair.ordermin, max: None },
+                cost: MinMax { min: pair.costmin, max: None },
+                leverage: MinMax { min: Some(Decimal::ONE), max: max_leverage.map(|l| Decimal::from(*l)) },
+                ..Default::default()
+            },
+            taker: taker_tiers.first().map(|t| t.fee),
+            maker: maker_tiers.first().map(|t| t.fee),
+            taker_tiers,
+            maker_tiers,
+            fee_side: FeeSide::Quote,
         }
+    }
+
+    /// Builds the balance from the BalanceEx entries; funds held by open orders (`hold_trade`) are used.
+    /// Margin of open positions is not attributed to a currency, see `TradeBalance`.
+    pub fn parse_balance(&self, balances: BTreeMap<String, ExtendedBalance>) -> AccountBalance {
+        let mut result = AccountBalance::default();
+        for (id, b) in balances {
+            result.add(self.currency_code(&id), b.balance, b.hold_trade.min(b.balance).max(Decimal::ZERO));
+        }
+        result
+    }
+
+    /// Kraken reports rolling 24h values at index 1 and does not timestamp tickers.
+    pub fn parse_ticker(&self, market_id: &str, info: &TickerInfo) -> Ticker {
+        let base_volume = info.v.get(1).copied();
+        let vwap = info.p.get(1).copied();
+        Ticker {
+            symbol: self.symbol(market_id),
+            bid: info.b.first().copied(),
+            ask: info.a.first().copied(),
+            last: info.c.first().copied(),
+            high: info.h.get(1).copied(),
+            low: info.l.get(1).copied(),
+            vwap,
+            open: info.o,
+            base_volume,
+            quote_volume: vwap.zip(base_volume).map(|(p, v)| p * v),
+            ..Default::default()
+        }.with_change()
+    }
+
+    pub fn parse_order_book(&self, market_id: &str, depth: &Depth) -> OrderBook {
+        let level = |l: &(Decimal, Decimal, i64)| OrderBookLevel { price: l.0, amount: l.1 };
+        let timestamp = depth.bids.iter()
+            .chain(depth.asks.iter())
+            .map(|l| l.2)
+            .max()
+            .and_then(|t| Utc.timestamp_opt(t, 0).single());
+        let mut book = OrderBook {
+            symbol: self.symbol(market_id),
+            bids: depth.bids.iter().map(level).collect(),
+            asks: depth.asks.iter().map(level).collect(),
+            timestamp,
+            nonce: None,
+        };
+        book.sort();
+        book
+    }
+
+    /// Kraken OHLC intervals are expressed in minutes.
+    pub fn ohlc_interval(timeframe: Timeframe) -> u32 {
+        timeframe.minutes()
+    }
+
+    pub fn parse_ohlcv(&self, entries: &[OhlcEntry]) -> Vec<Candle> {
+        entries.iter()
+            .filter_map(|e| Some(Candle {
+                timestamp: Utc.timestamp_opt(e.0, 0).single()?,
+                open: e.1,
+                high: e.2,
+                low: e.3,
+                close: e.4,
+                volume: e.6,
+            }))
+            .collect()
+    }
+
+    pub fn parse_trade(&self, market_id: &str, entry: &TradeEntry) -> Option<Trade> {
+        Some(Trade {
+            id: entry.6.map(|id| id.to_string()),
+            symbol: self.symbol(market_id),
+            timestamp: timestamp_from_secs(entry.2)?,
+            side: if entry.3 == "s" { Side::Sell } else { Side::Buy },
+            price: entry.0,
+            amount: entry.1,
+            cost: entry.0 * entry.1,
+            order_type: Some(if entry.4 == "m" { OrderType::Market } else { OrderType::Limit }),
+            order_id: None,
+            fee: None,
+            fee_currency: None,
+            taker_or_maker: None,
+        })
+    }
+
+    /// Kraken charges fees in the quote currency unless the order asked otherwise.
+    pub fn parse_my_trade(&self, id: &str, info: &TradeInfo) -> Option<Trade> {
+        let symbol = self.symbol(&info.pair);
+        let fee_currency = split_symbol(&symbol).ok().map(|(_, quote)| quote.to_string());
+        Some(Trade {
+            id: Some(id.to_string()),
+            timestamp: timestamp_from_secs(info.time)?,
+            side: if info.side == "sell" { Side::Sell } else { Side::Buy },
+            price: info.price,
+            amount: info.vol,
+            cost: info.cost,
+            order_type: Some(order_type_from_str(&info.ordertype)),
+            order_id: Some(info.ordertxid.clone()),
+            fee: Some(info.fee),
+            fee_currency,
+            taker_or_maker: info.maker.map(|m| if m { TakerOrMaker::Maker } else { TakerOrMaker::Taker }),
+            symbol,
+        })
+    }
+
+    fn parse_my_trades(&self, trades: &BTreeMap<String, TradeInfo>, symbol: Option<&str>) -> Vec<Trade> {
+        let mut result = trades.iter()
+            .filter_map(|(id, info)| self.parse_my_trade(id, info))
+            .filter(|t| symbol.is_none_or(|s| t.symbol == s))
+            .collect::<Vec<_>>();
+        result.sort_by_key(|t| t.timestamp);
+        result
+    }
+
+    pub fn parse_spread(&self, market_id: &str, entry: &SpreadEntry) -> Option<Spread> {
+        Some(Spread {
+            symbol: self.symbol(market_id),
+            timestamp: Utc.timestamp_opt(entry.0, 0).single()?,
+            bid: entry.1,
+            ask: entry.2,
+        })
+    }
+
+    /// Recent bid/ask spreads, the Spread endpoint only covers roughly the last 200 entries.
+    pub async fn fetch_spreads(&self, symbol: &str, since: Option<DateTime>) -> Result<Vec<Spread>> {
+        self.load_markets(false).await?;
+        let mut payload = HashMap::new();
+        payload.insert("pair", self.market_id(symbol)?);
+        if let Some(s) = since {
+            payload.insert("since", s.timestamp().to_string());
+        }
+        let data = self.get_data::<SpreadData>(& Functionality::Spread, payload).await?;
+        Ok(data.pairs.iter()
+            .flat_map(|(id, entries)| entries.iter().filter_map(move |e| self.parse_spread(id, e)))
+            .filter(|s| since.is_none_or(|since| s.timestamp >= since))
+            .collect())
+    }
+
+    /// AddOrder parameters; amounts and prices are rounded to the market precision when markets are loaded.
+    pub fn order_payload(&self, request: &OrderRequest) -> Result<HashMap<&'static str, String>> {
+        let market = self.markets.read().unwrap().market(&request.symbol).cloned();
+        let amount = market.as_ref().map_or(request.amount, |m| m.amount_to_precision(request.amount));
+        let price = |p: Option<Decimal>, name: &'static str| -> Result<String> {
+            let p = p.ok_or(Error::ArgumentsRequired(name))?;
+            Ok(market.as_ref().map_or(p, |m| m.price_to_precision(p)).to_string())
+        };
+
+        let mut payload = HashMap::new();
+        payload.insert("pair", self.market_id(&request.symbol)?);
+        payload.insert("type", side_to_str(request.side).to_string());
+        payload.insert("ordertype", order_type_to_str(request.order_type).to_string());
+        payload.insert("volume", amount.to_string());
+        match request.order_type {
+            OrderType::Market => {},
+            OrderType::Limit => {
+                payload.insert("price", price(request.price, "price")?);
+            },
+            OrderType::StopLoss | OrderType::TakeProfit => {
+                payload.insert("price", price(request.stop_price, "stop_price")?);
+            },
+            OrderType::StopLossLimit | OrderType::TakeProfitLimit => {
+                payload.insert("price", price(request.stop_price, "stop_price")?);
+                payload.insert("price2", price(request.price, "price")?);
+            }
+        }
+        if request.post_only {
+            payload.insert("oflags", "post".to_string());
+        }
+        match request.time_in_force {
+            Some(TimeInForce::GoodTillCancel) => {
+                payload.insert("timeinforce", "GTC".to_string());
+            },
+            Some(TimeInForce::ImmediateOrCancel) => {
+                payload.insert("timeinforce", "IOC".to_string());
+            },
+            Some(TimeInForce::GoodTillDate(expire)) => {
+                payload.insert("timeinforce", "GTD".to_string());
+                payload.insert("expiretm", expire.timestamp().to_string());
+            },
+            None => {}
+        }
+        if let Some(leverage) = request.leverage {
+            payload.insert("leverage", leverage.to_string());
+        }
+        if let Some(id) = &request.client_order_id {
+            payload.insert("cl_ord_id", id.clone());
+        }
+        if request.validate {
+            payload.insert("validate", "true".to_string());
+        }
+        Ok(payload)
+    }
+
+    /// Validated orders have no id and stay `Pending`.
+    pub fn parse_add_order(&self, request: &OrderRequest, result: &AddOrderResult) -> Order {
+        let id = result.txid.first().cloned().unwrap_or_default();
+        Order {
+            status: if id.is_empty() { OrderStatus::Pending } else { OrderStatus::Open },
+            id,
+            client_order_id: request.client_order_id.clone(),
+            symbol: request.symbol.clone(),
+            timestamp: Some(Utc::now()),
+            order_type: request.order_type,
+            side: request.side,
+            time_in_force: request.time_in_force,
+            post_only: request.post_only,
+            price: request.price,
+            stop_price: request.stop_price,
+            amount: request.amount,
+            filled: Decimal::ZERO,
+            remaining: request.amount,
+            cost: Decimal::ZERO,
+            average: None,
+            fee: None,
+            description: Some(result.descr.order.clone()),
+        }
+    }
+
+    pub fn parse_order(&self, id: &str, info: &OrderInfo) -> Order {
+        let order_type = order_type_from_str(&info.descr.ordertype);
+        let (price, stop_price) = match order_type {
+            OrderType::Market => (None, None),
+            OrderType::Limit => (info.descr.price, None),
+            OrderType::StopLoss | OrderType::TakeProfit => (None, info.descr.price),
+            OrderType::StopLossLimit | OrderType::TakeProfitLimit => (info.descr.price2, info.descr.price)
+        };
+        let time_in_force = info.expiretm
+            .filter(|t| !t.is_zero())
+            .and_then(timestamp_from_secs)
+            .map(TimeInForce::GoodTillDate);
+        let client_order_id = info.cl_ord_id.clone()
+            .or_else(|| info.userref.filter(|r| *r != 0).map(|r| r.to_string()));
+        Order {
+            id: id.to_string(),
+            client_order_id,
+            symbol: self.symbol(&info.descr.pair),
+            timestamp: timestamp_from_secs(info.opentm),
+            status: order_status_from_str(&info.status),
+            order_type,
+            side: if info.descr.side == "sell" { Side::Sell } else { Side::Buy },
+            time_in_force,
+            post_only: info.oflags.split(',').any(|f| f == "post"),
+            price,
+            stop_price,
+            amount: info.vol,
+            filled: info.vol_exec,
+            remaining: info.vol - info.vol_exec,
+            cost: info.cost,
+            average: if info.vol_exec.is_zero() { None } else { Some(info.price) },
+            fee: Some(info.fee),
+            description: Some(info.descr.order.clone()),
+        }
+    }
+
+    fn parse_orders(&self, orders: &BTreeMap<String, OrderInfo>, symbol: Option<&str>) -> Vec<Order> {
+        let mut result = orders.iter()
+            .map(|(id, info)| self.parse_order(id, info))
+            .filter(|o| symbol.is_none_or(|s| o.symbol == s))
+            .collect::<Vec<_>>();
+        result.sort_by_key(|o| o.timestamp);
+        result
+    }
+
+    pub fn parse_ledger_entry(&self, id: &str, info: &LedgerInfo) -> Option<LedgerEntry> {
+        let entry_type = match info.entry_type.as_str() {
+            "deposit" => LedgerEntryType::Deposit,
+            "withdrawal" => LedgerEntryType::Withdrawal,
+            "trade" | "spend" | "receive" => LedgerEntryType::Trade,
+            "margin" | "settled" => LedgerEntryType::Margin,
+            "rollover" => LedgerEntryType::Rollover,
+            "staking" | "dividend" => LedgerEntryType::Staking,
+            "transfer" => LedgerEntryType::Transfer,
+            other => LedgerEntryType::Other(other.to_string())
+        };
+        Some(LedgerEntry {
+            id: id.to_string(),
+            reference_id: Some(info.refid.clone()).filter(|r| !r.is_empty()),
+            timestamp: timestamp_from_secs(info.time)?,
+            entry_type,
+            currency: self.currency_code(&info.asset),
+            amount: info.amount,
+            fee: info.fee,
+            balance: info.balance,
+        })
+    }
+
+    fn parse_ledger(&self, entries: &BTreeMap<String, LedgerInfo>) -> Vec<LedgerEntry> {
+        let mut result = entries.iter()
+            .filter_map(|(id, info)| self.parse_ledger_entry(id, info))
+            .collect::<Vec<_>>();
+        result.sort_by_key(|e| e.timestamp);
+        result
+    }
+
+    pub fn parse_deposit_method(&self, info: &DepositMethodInfo) -> DepositMethod {
+        DepositMethod {
+            method: info.method.clone(),
+            limit: info.limit,
+            fee: info.fee,
+            address_setup_fee: info.address_setup_fee,
+            can_generate_address: info.gen_address,
+        }
+    }
+
+    pub fn parse_deposit_address(&self, code: &str, method: &str, info: &DepositAddressInfo) -> DepositAddress {
+        DepositAddress {
+            currency: code.to_string(),
+            method: method.to_string(),
+            address: info.address.clone(),
+            tag: info.tag.clone().or_else(|| info.memo.clone()),
+            expires: info.expiretm.filter(|t| !t.is_zero()).and_then(timestamp_from_secs),
+            new: info.new,
+        }
+    }
+
+    pub fn parse_transaction(&self, transaction_type: TransactionType, info: &TransactionInfo) -> Transaction {
+        let status = match (info.status.as_str(), info.status_prop.as_deref()) {
+            (_, Some("canceled")) => TransactionStatus::Canceled,
+            ("Success", _) => TransactionStatus::Ok,
+            ("Failure", _) => TransactionStatus::Failed,
+            _ => TransactionStatus::Pending
+        };
+        Transaction {
+            id: info.refid.clone(),
+            txid: Some(info.txid.clone()).filter(|t| !t.is_empty()),
+            transaction_type,
+            currency: self.currency_code(&info.asset),
+            method: info.method.clone(),
+            amount: info.amount,
+            fee: info.fee,
+            address: Some(info.info.clone()).filter(|a| !a.is_empty()),
+            status,
+            timestamp: timestamp_from_secs(Decimal::from(info.time)),
+        }
+    }
+
+    /// `value` and `net` are only present when requested with `docalcs`.
+    pub fn parse_position(&self, id: &str, info: &PositionInfo) -> Position {
+        let size = info.vol - info.vol_closed;
+        Position {
+            id: id.to_string(),
+            symbol: self.symbol(&info.pair),
+            timestamp: timestamp_from_secs(info.time),
+            side: if info.side == "sell" { PositionSide::Short } else { PositionSide::Long },
+            size,
+            entry_price: if info.vol.is_zero() { None } else { Some(info.cost / info.vol) },
+            cost: info.cost,
+            value: info.value,
+            unrealized_pnl: info.net,
+            margin: info.margin,
+            fee: info.fee,
+            rollover_time: info.rollovertm.filter(|t| !t.is_zero()).and_then(timestamp_from_secs),
+            rollover_terms: Some(info.terms.clone()).filter(|t| !t.is_empty()),
+        }
+    }
+
+    /// TradeVolume reports fees in percent.
+    pub fn parse_trading_fees(&self, volume: &TradeVolume) -> BTreeMap<String, TradingFee> {
+        volume.fees.iter()
+            .map(|(id, taker)| {
+                let maker = volume.fees_maker.get(id).unwrap_or(taker);
+                let symbol = self.symbol(id);
+                (symbol.clone(), TradingFee {
+                    symbol,
+                    maker: maker.fee / Decimal::ONE_HUNDRED,
+                    taker: taker.fee / Decimal::ONE_HUNDRED,
+                    volume: Some(volume.volume),
+                    volume_currency: Some(self.currency_code(&volume.currency)),
+                    tier_volume: taker.tiervolume,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn get_web_socket_token(&self) -> Result<WebSocketToken> {
         self.get_data_no_params::<WebSocketToken>(&Functionality::GetWebSocketsToken).await
     }
 }
 
+fn side_to_str(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell"
+    }
+}
+
+fn order_type_to_str(order_type: OrderType) -> &'static str {
+    match order_type {
+        OrderType::Market => "market",
+        OrderType::Limit => "limit",
+        OrderType::StopLoss => "stop-loss",
+        OrderType::TakeProfit => "take-profit",
+        OrderType::StopLossLimit => "stop-loss-limit",
+        OrderType::TakeProfitLimit => "take-profit-limit"
+    }
+}
+
+fn order_type_from_str(order_type: &str) -> OrderType {
+    match order_type {
+        "limit" => OrderType::Limit,
+        "stop-loss" => OrderType::StopLoss,
+        "take-profit" => OrderType::TakeProfit,
+        "stop-loss-limit" => OrderType::StopLossLimit,
+        "take-profit-limit" => OrderType::TakeProfitLimit,
+        _ => OrderType::Market
+    }
+}
+
+fn order_status_from_str(status: &str) -> OrderStatus {
+    match status {
+        "pending" => OrderStatus::Pending,
+        "open" => OrderStatus::Open,
+        "closed" => OrderStatus::Closed,
+        "canceled" => OrderStatus::Canceled,
+        "expired" => OrderStatus::Expired,
+        _ => OrderStatus::Rejected
+    }
+}
+
+/// Deposit and withdrawal limits are reported as `false` when there is no limit.
+fn decimal_or_false<'de, D>(deserializer: D) -> std::result::Result<Option<Decimal>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    match value {
+        serde_json::Value::String(_) | serde_json::Value::Number(_) => {
+            <Decimal as Deserialize>::deserialize(value).map(Some).map_err(serde::de::Error::custom)
+        },
+        _ => Ok(None)
+    }
+}
+
+/// Kraken reports times as fractional unix seconds.
+pub(crate) fn timestamp_from_secs(secs: Decimal) -> Option<DateTime> {
+    let nanos = (secs * Decimal::from(1_000_000_000)).trunc().to_i64()?;
+    Some(Utc.timestamp_nanos(nanos))
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Time {
-    #[serde(with = "rfc1123_date_format")]
-    rfc1123: DateTime,
-    unixtime: u64,
+    #[serde(with = "crate::rfc1123_date_format")]
+    pub rfc1123: DateTime,
+    pub unixtime: u64,
 }
 impl Default for Time {
     fn default() -> Self {
@@ -157,8 +925,8 @@ impl Default for Time {
 
 #[derive(Deserialize, Debug)]
 pub struct Status {
-    timestamp: DateTime,
-    status: String,
+    pub timestamp: DateTime,
+    pub status: String,
 }
 impl Default for Status {
     fn default() -> Self {
@@ -169,6 +937,345 @@ impl Default for Status {
     }
 }
 
+/// Balance of an asset as returned by BalanceEx.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ExtendedBalance {
+    pub balance: Decimal,
+    /// held by open orders
+    pub hold_trade: Decimal,
+}
+
+/// TradeBalance summary, all values are in the requested asset.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TradeBalance {
+    /// equivalent balance (combined balance of all currencies)
+    pub eb: Decimal,
+    /// trade balance (combined balance of all equity currencies)
+    pub tb: Decimal,
+    /// margin amount of open positions
+    pub m: Decimal,
+    /// unrealized net profit/loss of open positions
+    pub n: Decimal,
+    /// cost basis of open positions
+    pub c: Decimal,
+    /// current floating valuation of open positions
+    pub v: Decimal,
+    /// equity: trade balance + unrealized net profit/loss
+    pub e: Decimal,
+    /// free margin: equity - initial margin
+    pub mf: Decimal,
+    /// margin level: (equity / initial margin) * 100
+    pub ml: Option<Decimal>,
+}
+
+/// Ticker as returned by Kraken; array values are [today, last 24 hours]
+/// except for a/b ([price, whole lot volume, lot volume]) and c ([price, lot volume]).
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TickerInfo {
+    pub a: Vec<Decimal>,
+    pub b: Vec<Decimal>,
+    pub c: Vec<Decimal>,
+    pub v: Vec<Decimal>,
+    pub p: Vec<Decimal>,
+    pub t: Vec<u64>,
+    pub l: Vec<Decimal>,
+    pub h: Vec<Decimal>,
+    pub o: Option<Decimal>,
+}
+
+/// [time, open, high, low, close, vwap, volume, count]
+pub type OhlcEntry = (i64, Decimal, Decimal, Decimal, Decimal, Decimal, Decimal, u64);
+
+/// OHLC response, candles are keyed by pair id; `last` is the cursor for the next call.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct OhlcData {
+    pub last: i64,
+    #[serde(flatten)]
+    pub pairs: BTreeMap<String, Vec<OhlcEntry>>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct OrderDescription {
+    /// pair altname, e.g. XBTUSD
+    pub pair: String,
+    #[serde(rename = "type")]
+    pub side: String,
+    pub ordertype: String,
+    /// limit price, or trigger price of stop loss / take profit orders
+    pub price: Option<Decimal>,
+    /// limit price of stop loss limit / take profit limit orders
+    pub price2: Option<Decimal>,
+    pub leverage: String,
+    pub order: String,
+    pub close: Option<String>,
+}
+
+/// Order as returned by OpenOrders, ClosedOrders and QueryOrders.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct OrderInfo {
+    pub refid: Option<String>,
+    pub userref: Option<i64>,
+    pub cl_ord_id: Option<String>,
+    pub status: String,
+    pub opentm: Decimal,
+    pub closetm: Option<Decimal>,
+    pub expiretm: Option<Decimal>,
+    pub descr: OrderDescription,
+    pub vol: Decimal,
+    pub vol_exec: Decimal,
+    pub cost: Decimal,
+    pub fee: Decimal,
+    /// average price
+    pub price: Decimal,
+    pub oflags: String,
+    pub reason: Option<String>,
+    pub trades: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct OpenOrdersResult {
+    pub open: BTreeMap<String, OrderInfo>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ClosedOrdersResult {
+    pub closed: BTreeMap<String, OrderInfo>,
+    /// number of closed orders matching the criteria
+    pub count: usize,
+}
+
+/// Own trade as returned by TradesHistory and QueryTrades.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TradeInfo {
+    pub ordertxid: String,
+    pub postxid: String,
+    pub pair: String,
+    pub time: Decimal,
+    #[serde(rename = "type")]
+    pub side: String,
+    pub ordertype: String,
+    pub price: Decimal,
+    pub cost: Decimal,
+    pub fee: Decimal,
+    pub vol: Decimal,
+    pub margin: Decimal,
+    pub misc: String,
+    pub maker: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct TradesHistoryResult {
+    pub trades: BTreeMap<String, TradeInfo>,
+    /// number of trades matching the criteria
+    pub count: usize,
+}
+
+/// Ledger entry as returned by Ledgers and QueryLedgers.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct LedgerInfo {
+    pub refid: String,
+    pub time: Decimal,
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    pub subtype: String,
+    pub aclass: String,
+    pub asset: String,
+    pub amount: Decimal,
+    pub fee: Decimal,
+    pub balance: Option<Decimal>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct LedgersResult {
+    pub ledger: BTreeMap<String, LedgerInfo>,
+    /// number of ledger entries matching the criteria
+    pub count: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct DepositMethodInfo {
+    pub method: String,
+    #[serde(deserialize_with = "decimal_or_false")]
+    pub limit: Option<Decimal>,
+    pub fee: Option<Decimal>,
+    #[serde(rename = "address-setup-fee")]
+    pub address_setup_fee: Option<Decimal>,
+    #[serde(rename = "gen-address")]
+    pub gen_address: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct DepositAddressInfo {
+    pub address: String,
+    pub expiretm: Option<Decimal>,
+    pub new: bool,
+    pub tag: Option<String>,
+    pub memo: Option<String>,
+}
+
+/// Deposit or withdrawal as returned by DepositStatus and WithdrawStatus.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TransactionInfo {
+    pub method: String,
+    pub aclass: String,
+    pub asset: String,
+    pub refid: String,
+    pub txid: String,
+    /// address or account the funds were sent to
+    pub info: String,
+    pub amount: Decimal,
+    pub fee: Option<Decimal>,
+    pub time: i64,
+    pub status: String,
+    #[serde(rename = "status-prop")]
+    pub status_prop: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct WithdrawInfoResult {
+    pub method: String,
+    #[serde(deserialize_with = "decimal_or_false")]
+    pub limit: Option<Decimal>,
+    pub amount: Decimal,
+    pub fee: Decimal,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct WithdrawResult {
+    pub refid: String,
+}
+
+/// Open position as returned by OpenPositions.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PositionInfo {
+    pub ordertxid: String,
+    pub posstatus: String,
+    pub pair: String,
+    pub time: Decimal,
+    #[serde(rename = "type")]
+    pub side: String,
+    pub ordertype: String,
+    pub cost: Decimal,
+    pub fee: Decimal,
+    pub vol: Decimal,
+    pub vol_closed: Decimal,
+    pub margin: Decimal,
+    pub value: Option<Decimal>,
+    pub net: Option<Decimal>,
+    pub terms: String,
+    pub rollovertm: Option<Decimal>,
+    pub misc: String,
+    pub oflags: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FeeInfo {
+    /// current fee in percent
+    pub fee: Decimal,
+    pub minfee: Option<Decimal>,
+    pub maxfee: Option<Decimal>,
+    pub nextfee: Option<Decimal>,
+    pub nextvolume: Option<Decimal>,
+    pub tiervolume: Option<Decimal>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TradeVolume {
+    /// currency of the volume
+    pub currency: String,
+    /// 30 day trade volume
+    pub volume: Decimal,
+    pub fees: BTreeMap<String, FeeInfo>,
+    pub fees_maker: BTreeMap<String, FeeInfo>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct CancelOrderResult {
+    pub count: u32,
+    pub pending: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AddOrderResult {
+    pub descr: OrderDescription,
+    /// empty when the order was only validated
+    pub txid: Vec<String>,
+}
+
+/// [price, volume, time, buy/sell, market/limit, miscellaneous, trade id]
+#[derive(Deserialize, Debug, Clone)]
+pub struct TradeEntry(pub Decimal, pub Decimal, pub Decimal, pub String, pub String, pub String, #[serde(default)] pub Option<u64>);
+
+/// Trades response; `last` is the nanosecond cursor for the next call.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct TradesData {
+    pub last: String,
+    #[serde(flatten)]
+    pub pairs: BTreeMap<String, Vec<TradeEntry>>,
+}
+
+/// [time, bid, ask]
+pub type SpreadEntry = (i64, Decimal, Decimal);
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SpreadData {
+    pub last: i64,
+    #[serde(flatten)]
+    pub pairs: BTreeMap<String, Vec<SpreadEntry>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spread {
+    pub symbol: String,
+    pub timestamp: DateTime,
+    pub bid: Decimal,
+    pub ask: Decimal,
+}
+
+/// Order book levels as [price, volume, timestamp].
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Depth {
+    pub asks: Vec<(Decimal, Decimal, i64)>,
+    pub bids: Vec<(Decimal, Decimal, i64)>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AssetPair {
+    pub altname: String,
+    pub wsname: Option<String>,
+    pub base: String,
+    pub quote: String,
+    pub pair_decimals: Option<u32>,
+    pub lot_decimals: Option<u32>,
+    pub cost_decimals: Option<u32>,
+    pub leverage_buy: Vec<u32>,
+    pub leverage_sell: Vec<u32>,
+    /// [volume, percent fee]
+    pub fees: Vec<(Decimal, Decimal)>,
+    pub fees_maker: Vec<(Decimal, Decimal)>,
+    pub ordermin: Option<Decimal>,
+    pub costmin: Option<Decimal>,
+    pub status: Option<String>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename(deserialize = ""), rename_all = "camelCase")]
 pub struct Data<R> {
@@ -195,6 +1302,450 @@ impl ServerTime for Kraken {
 #[async_trait]
 impl Balance for Kraken {
     async fn get_balance(&self) -> Result<AccountBalance> {
-        self.get_data_no_params::<AccountBalance>(& Functionality::Balance).await      
+        let balances = self.get_data_no_params::<BTreeMap<String, ExtendedBalance>>(& Functionality::Balance).await?;
+        Ok(self.parse_balance(balances))
+    }
+}
+
+#[async_trait]
+impl Markets for Kraken {
+    async fn fetch_markets(&self) -> Result<Vec<Market>> {
+        let pairs = self.get_data_no_params::<BTreeMap<String, AssetPair>>(& Functionality::AssetPairs).await?;
+        Ok(self.parse_markets(pairs))
+    }
+
+    fn market_cache(&self) -> &RwLock<MarketCache> {
+        &self.markets
+    }
+}
+
+#[async_trait]
+impl Tickers for Kraken {
+    async fn fetch_ticker(&self, symbol: &str) -> Result<Ticker> {
+        let tickers = self.fetch_tickers(&[symbol]).await?;
+        tickers.into_values()
+            .next()
+            .ok_or(Error::ApiCallNoData())
     }
-}
\ No newline at end of file
+
+    /// All symbols are requested in a single call.
+    async fn fetch_tickers(&self, symbols: &[&str]) -> Result<BTreeMap<String, Ticker>> {
+        self.load_markets(false).await?;
+        let mut payload = HashMap::new();
+        if !symbols.is_empty() {
+            let ids = symbols.iter()
+                .map(|s| self.market_id(s))
+                .collect::<Result<Vec<_>>>()?;
+            payload.insert("pair", ids.join(","));
+        }
+        let infos = self.get_data::<BTreeMap<String, TickerInfo>>(& Functionality::Ticker, payload).await?;
+        let now = Utc::now();
+        Ok(infos.iter()
+            .map(|(id, info)| {
+                let mut t = self.parse_ticker(id, info);
+                t.timestamp = Some(now);
+                (t.symbol.clone(), t)
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl OrderBooks for Kraken {
+    async fn fetch_order_book(&self, symbol: &str, limit: Option<u32>) -> Result<OrderBook> {
+        self.load_markets(false).await?;
+        let mut payload = HashMap::new();
+        payload.insert("pair", self.market_id(symbol)?);
+        if let Some(count) = limit {
+            payload.insert("count", count.to_string());
+        }
+        let books = self.get_data::<BTreeMap<String, Depth>>(& Functionality::Depth, payload).await?;
+        books.iter()
+            .next()
+            .map(|(id, depth)| self.parse_order_book(id, depth))
+            .ok_or(Error::ApiCallNoData())
+    }
+}
+
+#[async_trait]
+impl Candles for Kraken {
+    /// Pages forward with the `last` cursor until `limit` candles are collected or no newer candles arrive.
+    async fn fetch_ohlcv(&self, symbol: &str, timeframe: Timeframe, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Candle>> {
+        self.load_markets(false).await?;
+        let id = self.market_id(symbol)?;
+        // Kraken returns candles after the cursor, step back one second to include `since`
+        let mut cursor = since.map(|s| s.timestamp() - 1);
+        let mut candles: Vec<Candle> = Vec::new();
+        loop {
+            let mut payload = HashMap::new();
+            payload.insert("pair", id.clone());
+            payload.insert("interval", Kraken::ohlc_interval(timeframe).to_string());
+            if let Some(c) = cursor {
+                payload.insert("since", c.to_string());
+            }
+            let data = self.get_data::<OhlcData>(& Functionality::OHLC, payload).await?;
+            let newest = candles.last().map(|c| c.timestamp);
+            let fresh = data.pairs.values()
+                .flat_map(|entries| self.parse_ohlcv(entries))
+                .filter(|c| newest.is_none_or(|n| c.timestamp > n) && since.is_none_or(|s| c.timestamp >= s))
+                .collect::<Vec<_>>();
+            if fresh.is_empty() {
+                break;
+            }
+            candles.extend(fresh);
+            if since.is_none() || limit.is_some_and(|l| candles.len() >= l) || cursor == Some(data.last) {
+                break;
+            }
+            cursor = Some(data.last);
+        }
+        limit_items(&mut candles, since, limit);
+        Ok(candles)
+    }
+}
+
+#[async_trait]
+impl Trades for Kraken {
+    /// Pages forward with the nanosecond `last` cursor until `limit` trades are collected or no newer trades arrive.
+    async fn fetch_trades(&self, symbol: &str, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Trade>> {
+        self.load_markets(false).await?;
+        let id = self.market_id(symbol)?;
+        let mut cursor = since.and_then(|s| s.timestamp_nanos_opt()).map(|n| n.to_string());
+        let mut trades: Vec<Trade> = Vec::new();
+        loop {
+            let mut payload = HashMap::new();
+            payload.insert("pair", id.clone());
+            if let Some(c) = &cursor {
+                payload.insert("since", c.clone());
+            }
+            let data = self.get_data::<TradesData>(& Functionality::Trades, payload).await?;
+            // the cursor is exclusive, pages do not overlap
+            let fresh = data.pairs.iter()
+                .flat_map(|(pair, entries)| entries.iter().filter_map(move |e| self.parse_trade(pair, e)))
+                .filter(|t| since.is_none_or(|s| t.timestamp >= s))
+                .collect::<Vec<_>>();
+            if fresh.is_empty() {
+                break;
+            }
+            trades.extend(fresh);
+            if since.is_none() || limit.is_some_and(|l| trades.len() >= l) || cursor.as_ref() == Some(&data.last) {
+                break;
+            }
+            cursor = Some(data.last);
+        }
+        limit_items(&mut trades, since, limit);
+        Ok(trades)
+    }
+}
+
+#[async_trait]
+impl Trading for Kraken {
+    async fn create_order(&self, request: &OrderRequest) -> Result<Order> {
+        self.load_markets(false).await?;
+        let payload = self.order_payload(request)?;
+        let pair = payload["pair"].clone();
+        if !request.validate {
+            self.order_counter(&pair).acquire(1).await;
+        }
+        let result = self.get_data::<AddOrderResult>(& Functionality::AddOrder, payload).await?;
+        let order = self.parse_add_order(request, &result);
+        if !order.id.is_empty() {
+            self.track_order(&order.id, &pair);
+        }
+        Ok(order)
+    }
+
+    async fn cancel_order(&self, id: &str) -> Result<()> {
+        let placed = self.placed_orders.lock().unwrap().remove(id);
+        if let Some((pair, at)) = placed {
+            self.order_counter(&pair).acquire(cancel_penalty(at.elapsed())).await;
+        }
+        let mut payload = HashMap::new();
+        payload.insert("txid", id.to_string());
+        self.get_data::<CancelOrderResult>(& Functionality::CancelOrder, payload).await?;
+        Ok(())
+    }
+
+    /// Cancels the open orders one by one, a failed cancel does not stop the others.
+    async fn cancel_all_orders(&self, symbol: Option<&str>) -> Result<BTreeMap<String, Result<()>>> {
+        let mut results = BTreeMap::new();
+        for order in self.fetch_open_orders(symbol).await? {
+            let result = self.cancel_order(&order.id).await;
+            results.insert(order.id, result);
+        }
+        Ok(results)
+    }
+
+    async fn fetch_order(&self, id: &str) -> Result<Order> {
+        self.fetch_orders(&[id]).await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::OrderNotFound(ApiMessage::new("QueryOrders", &format!("unknown order {}", id))))
+    }
+
+    /// Queries the orders in batches of up to 50 txids.
+    async fn fetch_orders(&self, ids: &[&str]) -> Result<Vec<Order>> {
+        self.load_markets(false).await?;
+        let mut orders = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(MAX_QUERY_ORDERS) {
+            let mut payload = HashMap::new();
+            payload.insert("txid", chunk.join(","));
+            payload.insert("trades", "true".to_string());
+            let result = self.get_data::<BTreeMap<String, OrderInfo>>(& Functionality::QueryOrders, payload).await?;
+            orders.extend(self.parse_orders(&result, None));
+        }
+        Ok(orders)
+    }
+
+    async fn fetch_open_orders(&self, symbol: Option<&str>) -> Result<Vec<Order>> {
+        self.load_markets(false).await?;
+        let result = self.get_data_no_params::<OpenOrdersResult>(& Functionality::OpenOrders).await?;
+        Ok(self.parse_orders(&result.open, symbol))
+    }
+
+    /// Kraken returns closed orders newest first in pages of 50. Without `since` pages are requested until
+    /// `limit` orders are collected; with `since` every page back to `since` is requested, the oldest come last.
+    /// Kraken cannot filter by pair, so a `symbol` that matches few orders may page through the whole history,
+    /// each page costing one private call.
+    async fn fetch_closed_orders(&self, symbol: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Order>> {
+        self.load_markets(false).await?;
+        let mut orders = Vec::new();
+        let mut offset = 0;
+        loop {
+            let mut payload = HashMap::new();
+            payload.insert("ofs", offset.to_string());
+            if let Some(s) = since {
+                payload.insert("start", s.timestamp().to_string());
+            }
+            let page = self.get_data::<ClosedOrdersResult>(& Functionality::ClosedOrders, payload).await?;
+            offset += page.closed.len();
+            orders.extend(self.parse_orders(&page.closed, symbol));
+            if page.closed.is_empty() || offset >= page.count || (since.is_none() && limit.is_some_and(|l| orders.len() >= l)) {
+                break;
+            }
+        }
+        orders.sort_by_key(|o| o.timestamp);
+        limit_items(&mut orders, since, limit);
+        Ok(orders)
+    }
+
+    /// Kraken returns trades newest first in pages of 50. Without `since` pages are requested until
+    /// `limit` trades are collected; with `since` every page back to `since` is requested, the oldest come last.
+    async fn fetch_my_trades(&self, symbol: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Trade>> {
+        self.load_markets(false).await?;
+        let mut trades = Vec::new();
+        let mut offset = 0;
+        loop {
+            let mut payload = HashMap::new();
+            payload.insert("ofs", offset.to_string());
+            if let Some(s) = since {
+                payload.insert("start", s.timestamp().to_string());
+            }
+            let page = self.get_data::<TradesHistoryResult>(& Functionality::TradesHistory, payload).await?;
+            offset += page.trades.len();
+            trades.extend(self.parse_my_trades(&page.trades, symbol));
+            if page.trades.is_empty() || offset >= page.count || (since.is_none() && limit.is_some_and(|l| trades.len() >= l)) {
+                break;
+            }
+        }
+        trades.sort_by_key(|t| t.timestamp);
+        limit_items(&mut trades, since, limit);
+        Ok(trades)
+    }
+
+    /// Queries the trades in batches of up to 20 ids.
+    async fn fetch_my_trades_by_ids(&self, ids: &[&str]) -> Result<Vec<Trade>> {
+        self.load_markets(false).await?;
+        let mut trades = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(MAX_QUERY_IDS) {
+            let mut payload = HashMap::new();
+            payload.insert("txid", chunk.join(","));
+            let result = self.get_data::<BTreeMap<String, TradeInfo>>(& Functionality::QueryTrades, payload).await?;
+            trades.extend(self.parse_my_trades(&result, None));
+        }
+        Ok(trades)
+    }
+}
+
+#[async_trait]
+impl Ledger for Kraken {
+    /// Kraken returns entries newest first in pages of 50. Without `since` pages are requested until
+    /// `limit` entries are collected; with `since` every page back to `since` is requested, the oldest come last.
+    async fn fetch_ledger(&self, code: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<LedgerEntry>> {
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        loop {
+            let mut payload = HashMap::new();
+            payload.insert("ofs", offset.to_string());
+            if let Some(c) = code {
+                payload.insert("asset", self.currency_id(c));
+            }
+            if let Some(s) = since {
+                payload.insert("start", s.timestamp().to_string());
+            }
+            let page = self.get_data::<LedgersResult>(& Functionality::Ledgers, payload).await?;
+            offset += page.ledger.len();
+            entries.extend(self.parse_ledger(&page.ledger));
+            if page.ledger.is_empty() || offset >= page.count || (since.is_none() && limit.is_some_and(|l| entries.len() >= l)) {
+                break;
+            }
+        }
+        entries.sort_by_key(|e| e.timestamp);
+        limit_items(&mut entries, since, limit);
+        Ok(entries)
+    }
+
+    /// Queries the entries in batches of up to 20 ids.
+    async fn fetch_ledger_entries_by_ids(&self, ids: &[&str]) -> Result<Vec<LedgerEntry>> {
+        let mut entries = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(MAX_QUERY_IDS) {
+            let mut payload = HashMap::new();
+            payload.insert("id", chunk.join(","));
+            let result = self.get_data::<BTreeMap<String, LedgerInfo>>(& Functionality::QueryLedgers, payload).await?;
+            entries.extend(self.parse_ledger(&result));
+        }
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl Funding for Kraken {
+    async fn fetch_deposit_methods(&self, code: &str) -> Result<Vec<DepositMethod>> {
+        let mut payload = HashMap::new();
+        payload.insert("asset", self.currency_id(code));
+        let methods = self.get_data::<Vec<DepositMethodInfo>>(& Functionality::DepositMethods, payload).await?;
+        Ok(methods.iter().map(|m| self.parse_deposit_method(m)).collect())
+    }
+
+    async fn fetch_deposit_address(&self, code: &str, method: Option<&str>, create_new: bool) -> Result<DepositAddress> {
+        let method = match method {
+            Some(m) => m.to_string(),
+            None => self.fetch_deposit_methods(code).await?
+                .into_iter()
+                .next()
+                .map(|m| m.method)
+                .ok_or(Error::ArgumentsRequired("method"))?
+        };
+        let mut payload = HashMap::new();
+        payload.insert("asset", self.currency_id(code));
+        payload.insert("method", method.clone());
+        if create_new {
+            payload.insert("new", "true".to_string());
+        }
+        let addresses = self.get_data::<Vec<DepositAddressInfo>>(& Functionality::DepositAddresses, payload).await?;
+        addresses.iter()
+            .find(|a| !create_new || a.new)
+            .map(|a| self.parse_deposit_address(code, &method, a))
+            .ok_or(Error::ApiCallNoData())
+    }
+
+    async fn fetch_deposits(&self, code: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Transaction>> {
+        let mut payload = HashMap::new();
+        if let Some(c) = code {
+            payload.insert("asset", self.currency_id(c));
+        }
+        if let Some(s) = since {
+            payload.insert("start", s.timestamp().to_string());
+        }
+        let infos = self.get_data::<Vec<TransactionInfo>>(& Functionality::DepositStatus, payload).await?;
+        let mut deposits = infos.iter()
+            .map(|i| self.parse_transaction(TransactionType::Deposit, i))
+            .filter(|d| since.is_none_or(|s| d.timestamp.is_some_and(|t| t >= s)))
+            .collect::<Vec<_>>();
+        deposits.sort_by_key(|d| d.timestamp);
+        limit_items(&mut deposits, since, limit);
+        Ok(deposits)
+    }
+
+    async fn withdraw(&self, code: &str, amount: Decimal, destination: &WithdrawalDestination) -> Result<String> {
+        let mut payload = HashMap::new();
+        payload.insert("asset", self.currency_id(code));
+        payload.insert("key", self.withdrawal_key(destination)?.to_string());
+        payload.insert("amount", amount.to_string());
+        let result = self.get_data::<WithdrawResult>(& Functionality::Withdraw, payload).await?;
+        Ok(result.refid)
+    }
+
+    async fn fetch_withdraw_info(&self, code: &str, amount: Decimal, destination: &WithdrawalDestination) -> Result<WithdrawalInfo> {
+        let mut payload = HashMap::new();
+        payload.insert("asset", self.currency_id(code));
+        payload.insert("key", self.withdrawal_key(destination)?.to_string());
+        payload.insert("amount", amount.to_string());
+        let info = self.get_data::<WithdrawInfoResult>(& Functionality::WithdrawInfo, payload).await?;
+        Ok(WithdrawalInfo {
+            method: info.method,
+            limit: info.limit,
+            amount: info.amount,
+            fee: info.fee,
+        })
+    }
+
+    async fn fetch_withdrawals(&self, code: Option<&str>, since: Option<DateTime>, limit: Option<usize>) -> Result<Vec<Transaction>> {
+        let mut payload = HashMap::new();
+        if let Some(c) = code {
+            payload.insert("asset", self.currency_id(c));
+        }
+        if let Some(s) = since {
+            payload.insert("start", s.timestamp().to_string());
+        }
+        let infos = self.get_data::<Vec<TransactionInfo>>(& Functionality::WithdrawStatus, payload).await?;
+        let mut withdrawals = infos.iter()
+            .map(|i| self.parse_transaction(TransactionType::Withdrawal, i))
+            .filter(|w| since.is_none_or(|s| w.timestamp.is_some_and(|t| t >= s)))
+            .collect::<Vec<_>>();
+        withdrawals.sort_by_key(|w| w.timestamp);
+        limit_items(&mut withdrawals, since, limit);
+        Ok(withdrawals)
+    }
+
+    async fn cancel_withdrawal(&self, code: &str, id: &str) -> Result<()> {
+        let mut payload = HashMap::new();
+        payload.insert("asset", self.currency_id(code));
+        payload.insert("refid", id.to_string());
+        self.get_data::<bool>(& Functionality::WithdrawCancel, payload).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Positions for Kraken {
+    async fn fetch_positions(&self, symbols: &[&str]) -> Result<Vec<Position>> {
+        self.load_markets(false).await?;
+        let mut payload = HashMap::new();
+        payload.insert("docalcs", "true".to_string());
+        let infos = self.get_data::<BTreeMap<String, PositionInfo>>(& Functionality::OpenPositions, payload).await?;
+        let mut positions = infos.iter()
+            .map(|(id, info)| self.parse_position(id, info))
+            .filter(|p| symbols.is_empty() || symbols.contains(&p.symbol.as_str()))
+            .collect::<Vec<_>>();
+        positions.sort_by_key(|p| p.timestamp);
+        Ok(positions)
+    }
+}
+
+#[async_trait]
+impl TradingFees for Kraken {
+    /// Without symbols the fees of every loaded market are requested.
+    async fn fetch_trading_fees(&self, symbols: &[&str]) -> Result<BTreeMap<String, TradingFee>> {
+        let markets = self.load_markets(false).await?;
+        let ids = if symbols.is_empty() {
+            markets.values().map(|m| m.id.clone()).collect::<Vec<_>>()
+        } else {
+            symbols.iter().map(|s| self.market_id(s)).collect::<Result<Vec<_>>>()?
+        };
+        let mut payload = HashMap::new();
+        payload.insert("pair", ids.join(","));
+        payload.insert("fee-info", "true".to_string());
+        let volume = self.get_data::<TradeVolume>(& Functionality::TradeVolume, payload).await?;
+        Ok(self.parse_trading_fees(&volume))
+    }
+}
+
+impl UnifiedExchange for Kraken {
+    fn exchange(&self) -> &Exchange {
+        &self.exchange
+    }
+
+    fn api(&self) -> &Api {
+        &self.api
+    }
+}
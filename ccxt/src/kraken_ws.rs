@@ -0,0 +1,575 @@
+use std::{collections::{BTreeMap, HashMap}, sync::{Arc, Mutex}, time::Duration};
+
+use futures::{SinkExt, StreamExt, channel::mpsc::{self, Receiver, Sender}, stream::{SplitSink, SplitStream}};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+use crate::{Decimal, Result, errors::{ApiMessage, Error}, exchange::*, kraken::{Kraken, Spread, TickerInfo, TradeEntry, timestamp_from_secs}};
+
+pub const WS_URL: &str = "wss://ws.kraken.com";
+
+/// Levels per side covered by the book checksum.
+const CHECKSUM_DEPTH: usize = 10;
+
+/// Kraken sends a heartbeat every second when no other message is due.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Items buffered per stream; a stream read slower than updates arrive ends with `Error::Lagged`.
+pub const STREAM_CAPACITY: usize = 1024;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Items of a subscription; an error is the last item before the stream ends.
+pub type WsStream<T> = Receiver<Result<T>>;
+
+/// Public market data of Kraken's WebSocket API, every subscription is a typed stream.
+/// Subscriptions are dropped on the exchange once all their streams are dropped.
+///
+/// There is no automatic reconnect: when the connection closes or the heartbeat stops every stream
+/// ends with `ExchangeNotAvailable` and so do new subscriptions. Callers have to `connect` again
+/// and resubscribe.
+#[derive(Debug, Clone)]
+pub struct KrakenWs {
+    kraken: Kraken,
+    sink: Arc<tokio::sync::Mutex<SplitSink<Socket, Message>>>,
+    state: Arc<Mutex<State>>,
+}
+
+impl KrakenWs {
+    /// Connects to the public endpoint; `kraken` translates between symbols and pair names.
+    pub async fn connect(kraken: Kraken) -> Result<Self> {
+        KrakenWs::connect_to(WS_URL, kraken).await
+    }
+
+    pub async fn connect_to(url: &str, kraken: Kraken) -> Result<Self> {
+        let (socket, _) = connect_async(url).await?;
+        let (sink, stream) = socket.split();
+        let ws = KrakenWs {
+            state: Arc::new(Mutex::new(State::new(kraken.clone()))),
+            kraken,
+            sink: Arc::new(tokio::sync::Mutex::new(sink)),
+        };
+        tokio::spawn(read(stream, ws.sink.clone(), ws.state.clone()));
+        Ok(ws)
+    }
+
+    /// Last system status sent by Kraken: online, maintenance, cancel_only or post_only.
+    pub fn status(&self) -> Option<String> {
+        self.state.lock().unwrap().status.clone()
+    }
+
+    /// Translates an unified symbol (BTC/USD) into the WebSocket pair name (XBT/USD).
+    pub fn ws_pair(&self, symbol: &str) -> Result<String> {
+        let (base, quote) = split_symbol(symbol)?;
+        Ok(format!("{}/{}", self.kraken.currency_id(base), self.kraken.currency_id(quote)))
+    }
+
+    pub async fn watch_ticker(&self, symbols: &[&str]) -> Result<WsStream<Ticker>> {
+        self.subscribe(symbols, "ticker".to_string(), Subscriber::Ticker).await
+    }
+
+    pub async fn watch_trades(&self, symbols: &[&str]) -> Result<WsStream<Trade>> {
+        self.subscribe(symbols, "trade".to_string(), Subscriber::Trade).await
+    }
+
+    /// Top `depth` levels (10, 25, 100, 500 or 1000) of the book, published after every update.
+    /// A book failing Kraken's checksum is resubscribed and published again from the new snapshot.
+    pub async fn watch_order_book(&self, symbols: &[&str], depth: u32) -> Result<WsStream<OrderBook>> {
+        self.subscribe(symbols, format!("book-{}", depth), Subscriber::Book).await
+    }
+
+    /// The candle in progress, published whenever it changes.
+    pub async fn watch_ohlcv(&self, symbols: &[&str], timeframe: Timeframe) -> Result<WsStream<Candle>> {
+        self.subscribe(symbols, format!("ohlc-{}", Kraken::ohlc_interval(timeframe)), Subscriber::Candle).await
+    }
+
+    pub async fn watch_spread(&self, symbols: &[&str]) -> Result<WsStream<Spread>> {
+        self.subscribe(symbols, "spread".to_string(), Subscriber::Spread).await
+    }
+
+    /// Pairs already subscribed on the connection share the existing subscription.
+    async fn subscribe<T>(&self, symbols: &[&str], channel: String, subscriber: fn(Sender<Result<T>>) -> Subscriber) -> Result<WsStream<T>> {
+        let pairs = symbols.iter().map(|s| self.ws_pair(s)).collect::<Result<Vec<_>>>()?;
+        let (tx, rx) = mpsc::channel(STREAM_CAPACITY);
+        let mut new_pairs = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(reason) = &state.closed {
+                return Err(Error::ExchangeNotAvailable(ApiMessage::new("ws", reason)));
+            }
+            for pair in pairs {
+                let subscribers = state.subscribers.entry((channel.clone(), pair.clone())).or_default();
+                if subscribers.is_empty() {
+                    new_pairs.push(pair);
+                }
+                subscribers.push(subscriber(tx.clone()));
+            }
+        }
+        if !new_pairs.is_empty() {
+            let message = json!({ "event": "subscribe", "pair": new_pairs, "subscription": subscription(&channel) });
+            self.sink.lock().await.send(Message::Text(message.to_string())).await?;
+        }
+        Ok(rx)
+    }
+}
+
+/// Dispatches messages until the connection closes or the heartbeat stops, the connection is not reopened.
+async fn read(mut stream: SplitStream<Socket>, sink: Arc<tokio::sync::Mutex<SplitSink<Socket, Message>>>, state: Arc<Mutex<State>>) {
+    let reason = 'read: loop {
+        let message = match tokio::time::timeout(HEARTBEAT_TIMEOUT, stream.next()).await {
+            Err(_) => break "heartbeat timeout".to_string(),
+            Ok(None) | Ok(Some(Ok(Message::Close(_)))) => break "connection closed".to_string(),
+            Ok(Some(Err(e))) => break e.to_string(),
+            Ok(Some(Ok(Message::Text(text)))) => text,
+            Ok(Some(Ok(_))) => continue,
+        };
+        let replies = state.lock().unwrap().handle(&message);
+        for reply in replies {
+            if let Err(e) = sink.lock().await.send(reply).await {
+                break 'read e.to_string();
+            }
+        }
+    };
+    state.lock().unwrap().close(&reason);
+}
+
+/// Subscription message of a channel name (ticker, book-10, ohlc-5).
+fn subscription(channel: &str) -> Value {
+    match channel.split_once('-') {
+        Some(("book", depth)) => json!({ "name": "book", "depth": depth.parse::<u32>().unwrap_or(10) }),
+        Some((name, interval)) => json!({ "name": name, "interval": interval.parse::<u32>().unwrap_or(1) }),
+        None => json!({ "name": channel }),
+    }
+}
+
+#[derive(Debug)]
+enum Subscriber {
+    Ticker(Sender<Result<Ticker>>),
+    Trade(Sender<Result<Trade>>),
+    Book(Sender<Result<OrderBook>>),
+    Candle(Sender<Result<Candle>>),
+    Spread(Sender<Result<Spread>>),
+}
+
+impl Subscriber {
+    fn is_closed(&self) -> bool {
+        match self {
+            Subscriber::Ticker(tx) => tx.is_closed(),
+            Subscriber::Trade(tx) => tx.is_closed(),
+            Subscriber::Book(tx) => tx.is_closed(),
+            Subscriber::Candle(tx) => tx.is_closed(),
+            Subscriber::Spread(tx) => tx.is_closed(),
+        }
+    }
+
+    /// Returns false when the stream is full.
+    fn publish(&mut self, update: &Update) -> bool {
+        match (self, update) {
+            (Subscriber::Ticker(tx), Update::Ticker(t)) => send(tx, t.clone()),
+            (Subscriber::Trade(tx), Update::Trades(trades)) => trades.iter().all(|t| send(tx, t.clone())),
+            (Subscriber::Book(tx), Update::Book(b)) => send(tx, b.clone()),
+            (Subscriber::Candle(tx), Update::Candle(c)) => send(tx, *c),
+            (Subscriber::Spread(tx), Update::Spread(s)) => send(tx, s.clone()),
+            _ => true,
+        }
+    }
+
+    /// Sends the error and ends the stream.
+    fn fail(self, error: Error) {
+        match self {
+            Subscriber::Ticker(tx) => send_error(tx, error),
+            Subscriber::Trade(tx) => send_error(tx, error),
+            Subscriber::Book(tx) => send_error(tx, error),
+            Subscriber::Candle(tx) => send_error(tx, error),
+            Subscriber::Spread(tx) => send_error(tx, error),
+        }
+    }
+}
+
+/// False when the stream is full; closed streams are pruned with the next update.
+fn send<T>(tx: &mut Sender<Result<T>>, item: T) -> bool {
+    !matches!(tx.try_send(Ok(item)), Err(e) if e.is_full())
+}
+
+/// A new sender always has room for one message, even when the stream is full.
+fn send_error<T>(tx: Sender<Result<T>>, error: Error) {
+    let _ = tx.clone().try_send(Err(error));
+}
+
+#[derive(Debug)]
+enum Update {
+    Ticker(Ticker),
+    Trades(Vec<Trade>),
+    Book(OrderBook),
+    /// the checksum of the local book did not match
+    BookOutOfSync,
+    Candle(Candle),
+    Spread(Spread),
+}
+
+/// Subscribers and order books keyed by (channel name, pair).
+#[derive(Debug)]
+struct State {
+    kraken: Kraken,
+    status: Option<String>,
+    closed: Option<String>,
+    subscribers: HashMap<(String, String), Vec<Subscriber>>,
+    books: HashMap<(String, String), LocalBook>,
+}
+
+impl State {
+    fn new(kraken: Kraken) -> Self {
+        State {
+            kraken,
+            status: None,
+            closed: None,
+            subscribers: HashMap::new(),
+            books: HashMap::new(),
+        }
+    }
+
+    /// Handles a text message, returns the messages to send back.
+    fn handle(&mut self, text: &str) -> Vec<Message> {
+        match serde_json::from_str::<Value>(text) {
+            Ok(Value::Array(items)) => self.data(items),
+            Ok(event) => {
+                match Event::deserialize(&event) {
+                    Ok(Event::SystemStatus(s)) => self.status = Some(s.status),
+                    Ok(Event::SubscriptionStatus(s)) => self.subscription_status(s),
+                    _ => {},
+                }
+                Vec::new()
+            },
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Failed subscriptions end the streams waiting for them.
+    fn subscription_status(&mut self, status: SubscriptionStatus) {
+        if status.status != "error" {
+            return;
+        }
+        let subscription = status.subscription;
+        let channel = status.channel_name.or_else(|| subscription.map(|s| s.channel())).unwrap_or_default();
+        let key = (channel, status.pair.unwrap_or_default());
+        let message = status.error_message.unwrap_or(status.status);
+        self.books.remove(&key);
+        for s in self.subscribers.remove(&key).unwrap_or_default() {
+            s.fail(Error::ApiCallError(ApiMessage::new("subscriptionStatus", &message)));
+        }
+    }
+
+    /// Channel messages are [channel id, payload.., channel name, pair].
+    fn data(&mut self, mut items: Vec<Value>) -> Vec<Message> {
+        if items.len() < 4 {
+            return Vec::new();
+        }
+        let (pair, channel) = match (items.pop(), items.pop()) {
+            (Some(Value::String(p)), Some(Value::String(c))) => (p, c),
+            _ => return Vec::new(),
+        };
+        let key = (channel, pair);
+        let subscribers = match self.subscribers.get_mut(&key) {
+            Some(s) => s,
+            None => return Vec::new(),
+        };
+        subscribers.retain(|s| !s.is_closed());
+        if subscribers.is_empty() {
+            return vec![self.unsubscribe(&key)];
+        }
+        match self.parse_update(&key, &items[1..]) {
+            Ok(None) => {},
+            Ok(Some(Update::BookOutOfSync)) => return self.resubscribe(&key),
+            Ok(Some(update)) => {
+                let subscribers = self.subscribers.remove(&key).unwrap_or_default();
+                let mut published = Vec::with_capacity(subscribers.len());
+                for mut s in subscribers {
+                    if s.publish(&update) {
+                        published.push(s);
+                    } else {
+                        s.fail(Error::Lagged(format!("{} {}", key.0, key.1)));
+                    }
+                }
+                if published.is_empty() {
+                    return vec![self.unsubscribe(&key)];
+                }
+                self.subscribers.insert(key, published);
+            },
+            Err(e) => {
+                let message = e.to_string();
+                self.books.remove(&key);
+                for s in self.subscribers.remove(&key).unwrap_or_default() {
+                    s.fail(Error::ApiCallError(ApiMessage::new("channel", &message)));
+                }
+            },
+        }
+        Vec::new()
+    }
+
+    /// Forgets the subscription, returns the message ending it on the exchange.
+    fn unsubscribe(&mut self, key: &(String, String)) -> Message {
+        self.subscribers.remove(key);
+        self.books.remove(key);
+        let message = json!({ "event": "unsubscribe", "pair": [key.1], "subscription": subscription(&key.0) });
+        Message::Text(message.to_string())
+    }
+
+    /// Subscribes again to get a new snapshot, the streams stay open.
+    fn resubscribe(&self, key: &(String, String)) -> Vec<Message> {
+        ["unsubscribe", "subscribe"].iter()
+            .map(|event| Message::Text(json!({ "event": event, "pair": [key.1], "subscription": subscription(&key.0) }).to_string()))
+            .collect()
+    }
+
+    /// None when there is nothing to publish, i.e. a book waiting for its snapshot.
+    fn parse_update(&mut self, key: &(String, String), payloads: &[Value]) -> Result<Option<Update>> {
+        let (channel, pair) = key;
+        let symbol = self.symbol(pair);
+        let payload = payloads.first().ok_or(Error::ApiCallNoData())?;
+        match channel.split_once('-').map_or(channel.as_str(), |(name, _)| name) {
+            "ticker" => {
+                let ticker = self.kraken.parse_ticker(pair, &TickerData::deserialize(payload)?.into());
+                Ok(Some(Update::Ticker(Ticker { symbol, ..ticker })))
+            },
+            "trade" => Ok(Some(Update::Trades(Vec::<TradeEntry>::deserialize(payload)?.iter()
+                .filter_map(|e| self.kraken.parse_trade(pair, e))
+                .map(|t| Trade { symbol: symbol.clone(), ..t })
+                .collect()))),
+            "spread" => {
+                let entry = SpreadUpdate::deserialize(payload)?;
+                Ok(Some(Update::Spread(Spread {
+                    symbol,
+                    timestamp: timestamp_from_secs(entry.2).ok_or_else(|| Error::ApiCallError(ApiMessage::new("spread", &format!("invalid time {}", entry.2))))?,
+                    bid: entry.0,
+                    ask: entry.1,
+                })))
+            },
+            "ohlc" => {
+                let entry = OhlcUpdate::deserialize(payload)?;
+                let interval = channel.split_once('-').and_then(|(_, i)| i.parse::<i64>().ok()).unwrap_or(1);
+                let start = entry.1 - Decimal::from(interval * 60);
+                Ok(Some(Update::Candle(Candle {
+                    timestamp: timestamp_from_secs(start).ok_or_else(|| Error::ApiCallError(ApiMessage::new("ohlc", &format!("invalid time {}", entry.1))))?,
+                    open: entry.2,
+                    high: entry.3,
+                    low: entry.4,
+                    close: entry.5,
+                    volume: entry.7,
+                })))
+            },
+            "book" => {
+                let depth = channel.split_once('-').and_then(|(_, d)| d.parse().ok()).unwrap_or(10);
+                let book = self.books.entry(key.clone()).or_insert_with(|| LocalBook::new(depth));
+                let mut checksum = None;
+                for p in payloads {
+                    let data = BookData::deserialize(p)?;
+                    checksum = data.checksum.clone().or(checksum);
+                    book.apply(data);
+                }
+                if !book.synced {
+                    return Ok(None);
+                }
+                if checksum.is_some_and(|c| c.parse::<u32>().ok() != Some(book.checksum())) {
+                    *book = LocalBook::new(depth);
+                    return Ok(Some(Update::BookOutOfSync));
+                }
+                Ok(Some(Update::Book(book.order_book(&symbol))))
+            },
+            _ => Err(Error::ApiFunctionNotSupported("channel")),
+        }
+    }
+
+    /// Translates a WebSocket pair name (XBT/USD) into an unified symbol (BTC/USD).
+    fn symbol(&self, pair: &str) -> String {
+        match pair.split_once('/') {
+            Some((base, quote)) => format!("{}/{}", self.kraken.currency_code(base), self.kraken.currency_code(quote)),
+            None => self.kraken.symbol(pair),
+        }
+    }
+
+    /// Ends every stream after the connection is lost.
+    fn close(&mut self, reason: &str) {
+        self.closed = Some(reason.to_string());
+        self.books.clear();
+        for s in self.subscribers.drain().flat_map(|(_, s)| s) {
+            s.fail(Error::ExchangeNotAvailable(ApiMessage::new("ws", reason)));
+        }
+    }
+}
+
+/// Order book kept up to date from the snapshot and the following updates.
+/// Prices and volumes keep the scale Kraken sent them with, the checksum depends on it.
+#[derive(Debug)]
+struct LocalBook {
+    depth: usize,
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+    time: Decimal,
+    /// a snapshot was applied, updates before it are ignored
+    synced: bool,
+}
+
+impl LocalBook {
+    fn new(depth: usize) -> Self {
+        LocalBook {
+            depth,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            time: Decimal::ZERO,
+            synced: false,
+        }
+    }
+
+    /// Levels with zero volume are removed, the book is then cut back to its depth.
+    fn apply(&mut self, data: BookData) {
+        if !data.asks_snapshot.is_empty() || !data.bids_snapshot.is_empty() {
+            self.asks.clear();
+            self.bids.clear();
+            self.synced = true;
+        } else if !self.synced {
+            return;
+        }
+        let asks = data.asks_snapshot.iter().chain(data.asks.iter());
+        let bids = data.bids_snapshot.iter().chain(data.bids.iter());
+        for (side, levels) in [(&mut self.asks, asks), (&mut self.bids, bids)] {
+            for l in levels {
+                if l.1.is_zero() {
+                    side.remove(&l.0);
+                } else {
+                    side.insert(l.0, l.1);
+                }
+                self.time = self.time.max(l.2);
+            }
+        }
+        while self.asks.len() > self.depth {
+            self.asks.pop_last();
+        }
+        while self.bids.len() > self.depth {
+            self.bids.pop_first();
+        }
+    }
+
+    /// CRC32 of price and volume of the top 10 asks, then the top 10 bids, each without the decimal
+    /// point and leading zeros.
+    fn checksum(&self) -> u32 {
+        let digits = |d: &Decimal| d.to_string().replace('.', "").trim_start_matches('0').to_string();
+        let levels = self.asks.iter().take(CHECKSUM_DEPTH).chain(self.bids.iter().rev().take(CHECKSUM_DEPTH));
+        let mut hasher = crc32fast::Hasher::new();
+        for (price, volume) in levels {
+            hasher.update(digits(price).as_bytes());
+            hasher.update(digits(volume).as_bytes());
+        }
+        hasher.finalize()
+    }
+
+    fn order_book(&self, symbol: &str) -> OrderBook {
+        OrderBook {
+            symbol: symbol.to_string(),
+            bids: self.bids.iter().rev().map(|(p, a)| OrderBookLevel { price: *p, amount: *a }).collect(),
+            asks: self.asks.iter().map(|(p, a)| OrderBookLevel { price: *p, amount: *a }).collect(),
+            timestamp: timestamp_from_secs(self.time),
+            nonce: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "event", rename_all = "camelCase")]
+enum Event {
+    SystemStatus(SystemStatusEvent),
+    SubscriptionStatus(SubscriptionStatus),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SystemStatusEvent {
+    status: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SubscriptionStatus {
+    channel_name: Option<String>,
+    pair: Option<String>,
+    status: String,
+    subscription: Option<SubscriptionInfo>,
+    error_message: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SubscriptionInfo {
+    name: String,
+    depth: Option<u32>,
+    interval: Option<u32>,
+}
+
+impl SubscriptionInfo {
+    fn channel(&self) -> String {
+        match self.depth.or(self.interval) {
+            Some(n) => format!("{}-{}", self.name, n),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Ticker as sent over WebSocket, `o` is [today, last 24 hours] here.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TickerData {
+    pub a: Vec<Decimal>,
+    pub b: Vec<Decimal>,
+    pub c: Vec<Decimal>,
+    pub v: Vec<Decimal>,
+    pub p: Vec<Decimal>,
+    pub t: Vec<u64>,
+    pub l: Vec<Decimal>,
+    pub h: Vec<Decimal>,
+    pub o: Vec<Decimal>,
+}
+
+impl From<TickerData> for TickerInfo {
+    fn from(t: TickerData) -> Self {
+        TickerInfo {
+            o: t.o.get(1).copied(),
+            a: t.a,
+            b: t.b,
+            c: t.c,
+            v: t.v,
+            p: t.p,
+            t: t.t,
+            l: t.l,
+            h: t.h,
+        }
+    }
+}
+
+/// [bid, ask, time, bid volume, ask volume]
+#[derive(Deserialize, Debug)]
+pub struct SpreadUpdate(pub Decimal, pub Decimal, pub Decimal, pub Decimal, pub Decimal);
+
+/// [time, end time, open, high, low, close, vwap, volume, count]
+#[derive(Deserialize, Debug)]
+pub struct OhlcUpdate(pub Decimal, pub Decimal, pub Decimal, pub Decimal, pub Decimal, pub Decimal, pub Decimal, pub Decimal, pub u64);
+
+/// [price, volume, time, update type]
+#[derive(Deserialize, Debug)]
+pub struct BookLevel(pub Decimal, pub Decimal, pub Decimal, #[serde(default)] pub Option<String>);
+
+/// Snapshots carry `as`/`bs`, updates `a`/`b` and the checksum `c` of the book after the update.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct BookData {
+    #[serde(rename = "as")]
+    pub asks_snapshot: Vec<BookLevel>,
+    #[serde(rename = "bs")]
+    pub bids_snapshot: Vec<BookLevel>,
+    #[serde(rename = "a")]
+    pub asks: Vec<BookLevel>,
+    #[serde(rename = "b")]
+    pub bids: Vec<BookLevel>,
+    #[serde(rename = "c")]
+    pub checksum: Option<String>,
+}
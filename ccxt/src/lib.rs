@@ -10,11 +10,19 @@ extern crate async_trait;
 pub mod errors;
 pub mod exchange;
 pub mod coinbase;
+pub mod coinbase_advanced;
 pub mod kraken;
+pub mod kraken_ws;
+pub mod nonce;
+pub mod rate_limiter;
+pub mod registry;
+pub mod retry;
 pub mod rfc1123_date_format;
 
 pub type DateTime = chrono::DateTime<chrono::Utc>;
 
+pub type Decimal = rust_decimal::Decimal;
+
 pub type Result<R> = anyhow::Result<R, errors::Error>;
 
 pub type ApiRequest = reqwest::RequestBuilder;
\ No newline at end of file
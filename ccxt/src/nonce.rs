@@ -0,0 +1,93 @@
+use std::{fmt::Debug, fs::{File, OpenOptions}, io::{self, Read, Seek, SeekFrom, Write}, path::PathBuf, sync::atomic::{AtomicU64, Ordering}};
+
+use chrono::Utc;
+use fs2::FileExt;
+use crate::Result;
+
+/// Source of the strictly increasing nonces signed into private requests.
+pub trait NonceSource: Debug + Send + Sync {
+    /// Next nonce, greater than every nonce returned before.
+    fn next(&self) -> Result<u64>;
+
+    /// Makes the following nonces greater than `floor`, e.g. the exchange time after a rejected nonce.
+    /// Nonces another source already sent above `floor` are unknown here and are not caught up with.
+    fn resync(&self, floor: u64) -> Result<()>;
+}
+
+fn now_millis() -> u64 {
+    Utc::now().timestamp_millis() as u64
+}
+
+/// In process nonces: the current time in milliseconds, or the previous nonce + 1 when the clock
+/// has not advanced or went backward.
+#[derive(Debug, Default)]
+pub struct AtomicNonce {
+    last: AtomicU64,
+}
+
+impl AtomicNonce {
+    pub fn new() -> Self {
+        AtomicNonce::default()
+    }
+}
+
+impl NonceSource for AtomicNonce {
+    fn next(&self) -> Result<u64> {
+        let now = now_millis();
+        let last = self.last.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(now.max(last + 1))).unwrap();
+        Ok(now.max(last + 1))
+    }
+
+    fn resync(&self, floor: u64) -> Result<()> {
+        self.last.fetch_max(floor, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Nonces persisted in a locked file, so processes sharing one API key and restarts stay monotonic.
+#[derive(Debug)]
+pub struct FileNonce {
+    path: PathBuf,
+}
+
+impl FileNonce {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        FileNonce { path: path.into() }
+    }
+
+    /// Replaces the stored nonce with `update(stored)` while holding an exclusive lock on the file.
+    fn update(&self, update: impl FnOnce(u64) -> u64) -> Result<u64> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&self.path)?;
+        FileExt::lock_exclusive(&file)?;
+        let result = FileNonce::rewrite(&mut file, update);
+        FileExt::unlock(&file)?;
+        result
+    }
+
+    fn rewrite(file: &mut File, update: impl FnOnce(u64) -> u64) -> Result<u64> {
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let stored = match content.trim() {
+            "" => 0,
+            s => s.parse::<u64>().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        };
+        let value = update(stored);
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(value.to_string().as_bytes())?;
+        file.sync_data()?;
+        Ok(value)
+    }
+}
+
+impl NonceSource for FileNonce {
+    fn next(&self) -> Result<u64> {
+        let now = now_millis();
+        self.update(|last| now.max(last + 1))
+    }
+
+    fn resync(&self, floor: u64) -> Result<()> {
+        self.update(|last| last.max(floor))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use tokio::time::{Instant, sleep, sleep_until};
+
+/// Spaces requests so that a request of cost `n` occupies `n` intervals.
+/// Clones share the schedule, so parallel tasks using clones of one client are throttled together.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    interval: Duration,
+    /// earliest time the next request may start, unset before the first request
+    next: Arc<Mutex<Option<Instant>>>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        RateLimiter {
+            interval,
+            next: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Limiter for `Exchange::rate_limit` milliseconds, unlimited when it is not set.
+    pub fn from_millis(rate_limit: Option<u32>) -> Self {
+        RateLimiter::new(Duration::from_millis(rate_limit.unwrap_or(0) as u64))
+    }
+
+    /// Waits until a request of the given cost may be sent; requests of cost 0 are not throttled.
+    pub async fn acquire(&self, cost: u32) {
+        if cost == 0 || self.interval.is_zero() {
+            return;
+        }
+        let start = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let start = next.map_or(now, |n| n.max(now));
+            *next = Some(start + self.interval * cost);
+            start
+        };
+        sleep_until(start).await;
+    }
+}
+
+/// Counter that grows by the cost of every call and decays linearly over time.
+/// Calls wait until the counter is back under its maximum; clones share the counter.
+#[derive(Debug, Clone)]
+pub struct DecayingCounter {
+    max: f64,
+    /// decrease of the counter per second
+    decay: f64,
+    /// counter value at the instant, above `max` while calls are waiting
+    state: Arc<Mutex<(f64, Option<Instant>)>>,
+}
+
+impl DecayingCounter {
+    pub fn new(max: u32, decay: f64) -> Self {
+        DecayingCounter {
+            max: max as f64,
+            decay,
+            state: Arc::new(Mutex::new((0.0, None))),
+        }
+    }
+
+    /// Adds `cost` to the counter, waiting until it decays back to the maximum when exceeded.
+    pub async fn acquire(&self, cost: u32) {
+        if cost == 0 {
+            return;
+        }
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let decayed = state.1.map_or(0.0, |at| (state.0 - now.duration_since(at).as_secs_f64() * self.decay).max(0.0));
+            let value = decayed + cost as f64;
+            *state = (value, Some(now));
+            Duration::from_secs_f64(((value - self.max) / self.decay).max(0.0))
+        };
+        sleep(wait).await;
+    }
+}
@@ -0,0 +1,14 @@
+use crate::{Result, coinbase::Coinbase, coinbase_advanced::CoinbaseAdvanced, errors::Error, exchange::{ApiConfig, UnifiedExchange}, kraken::Kraken};
+
+/// Ids accepted by `create_exchange`.
+pub const EXCHANGES: [&str; 3] = ["coinbase", "coinbaseadvanced", "kraken"];
+
+/// Creates the exchange registered under `id`; public only calls work with `ApiConfig::default()`.
+pub fn create_exchange(id: &str, config: ApiConfig) -> Result<Box<dyn UnifiedExchange>> {
+    match id {
+        "coinbase" => Ok(Box::new(Coinbase::new("coinbase", config.key, config.secret))),
+        "coinbaseadvanced" => Ok(Box::new(CoinbaseAdvanced::new("coinbaseadvanced", config.key, config.secret)?)),
+        "kraken" => Ok(Box::new(Kraken::new("kraken", config.key, config.secret))),
+        _ => Err(Error::UnknownExchange(id.to_string())),
+    }
+}
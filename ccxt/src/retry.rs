@@ -0,0 +1,96 @@
+use std::{error::Error as StdError, future::Future, io, time::Duration};
+
+use rand::Rng;
+use reqwest::StatusCode;
+use tokio::time::sleep;
+use crate::{Result, errors::Error};
+
+/// Exponential backoff with jitter for requests that failed transiently.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// retries after the first attempt, 0 disables retrying
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        RetryPolicy { max_retries, base_delay, max_delay }
+    }
+
+    pub fn none() -> Self {
+        RetryPolicy { max_retries: 0, ..Default::default() }
+    }
+
+    /// Delay before the retry following `attempt` (0 based): between half and the whole of
+    /// `base_delay * 2^attempt`, capped at `max_delay`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.base_delay
+            .checked_mul(2u32.saturating_pow(attempt))
+            .map_or(self.max_delay, |d| d.min(self.max_delay));
+        let half = backoff / 2;
+        half + half.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+
+    /// Runs `request` until it succeeds, fails with an error `retryable` rejects or the retries run out.
+    pub async fn retry<T, F, Fut>(&self, retryable: impl Fn(&Error) -> bool, mut request: F) -> Result<T>
+    where F: FnMut() -> Fut,
+          Fut: Future<Output = Result<T>>
+    {
+        let mut attempt = 0;
+        loop {
+            match request().await {
+                Err(e) if attempt < self.max_retries && retryable(&e) => {
+                    sleep(self.delay(attempt)).await;
+                    attempt += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Timeouts, dropped connections, HTTP 5xx and 429 and unavailable exchange.
+/// Rate limits reported in the response body are not retried, the backoff is shorter than the time
+/// the exchange counters take to decay.
+pub fn is_transient(error: &Error) -> bool {
+    let e = match error {
+        Error::Http(e) => e,
+        Error::ExchangeNotAvailable(_) => return true,
+        _ => return false,
+    };
+    if e.is_timeout() || e.is_connect() {
+        return true;
+    }
+    if let Some(status) = e.status() {
+        return status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+    }
+    let mut source = e.source();
+    while let Some(s) = source {
+        if let Some(io) = s.downcast_ref::<io::Error>() {
+            return matches!(io.kind(), io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::BrokenPipe | io::ErrorKind::UnexpectedEof);
+        }
+        source = s.source();
+    }
+    false
+}
+
+/// Turns HTTP 5xx and 429 responses into errors so they can be retried.
+pub fn error_for_transient_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response.error_for_status()?);
+    }
+    Ok(response)
+}
@@ -0,0 +1,95 @@
+mod coinbase_tests
+{
+    use ccxt::coinbase::*;
+    use ccxt::exchange::*;
+    use ccxt::Decimal;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    #[test]
+    fn parse_markets() {
+        let fiat: Vec<Currency> = serde_json::from_str(r#"[
+            {"id": "USD", "name": "US Dollar", "min_size": "0.01000000"},
+            {"id": "EUR", "name": "Euro", "min_size": "0.01000000"}
+        ]"#).unwrap();
+        let crypto: Vec<CryptoCurrency> = serde_json::from_str(r##"[
+            {"asset_id": "5b71fc48-3dd3-540c-809b-f8c94d0e68b5", "code": "BTC", "name": "Bitcoin",
+             "color": "#F7931A", "sort_index": 100, "exponent": 8, "type": "crypto"}
+        ]"##).unwrap();
+
+        let markets = Coinbase::new("coinbase", String::new(), String::new()).parse_markets(&fiat, &crypto);
+        assert_eq!(2, markets.len());
+        assert_eq!("BTC-USD", markets[0].id);
+        assert_eq!("BTC/USD", markets[0].symbol);
+        assert_eq!(None, markets[0].active);
+        assert_eq!(Some(8), markets[0].precision.amount);
+        assert_eq!(Some(2), markets[0].precision.price);
+        assert_eq!("BTC/EUR", markets[1].symbol);
+    }
+
+    #[test]
+    fn parse_balance() {
+        let accounts: Vec<Account> = serde_json::from_str(r#"[
+            {"id": "58542935-67b5-56e1-a3f9-42686e07fa40", "name": "My Vault", "primary": false, "type": "vault",
+             "currency": "BTC", "balance": {"amount": "4.00000000", "currency": "BTC"}},
+            {"id": "2bbf394c-193b-5b2a-9155-3b4732659ede", "name": "My Wallet", "primary": true, "type": "wallet",
+             "currency": "BTC", "balance": {"amount": "39.59000000", "currency": "BTC"}},
+            {"id": "68bfd2a6-2d06-5f4b-9b3c-1e3a1b1a2b3c", "name": "USD Wallet", "primary": false, "type": "fiat",
+             "currency": "USD", "balance": {"amount": "10.00", "currency": "USD"}}
+        ]"#).unwrap();
+
+        let b = Coinbase::new("coinbase", String::new(), String::new()).parse_balance(&accounts);
+        assert_eq!(Decimal::from_str("43.59").unwrap(), b.get("BTC").unwrap().total);
+        assert_eq!(Decimal::from_str("43.59").unwrap(), b.get("BTC").unwrap().free);
+        assert_eq!(Decimal::from_str("10").unwrap(), b.get("USD").unwrap().total);
+    }
+
+    #[test]
+    fn price_request_expands_currency_pair() {
+        let c = Coinbase::new("coinbase", String::new(), String::new());
+        let mut payload = HashMap::new();
+        payload.insert("currency_pair", c.market_id("BTC/USD").unwrap());
+        let request = c.get_request(&Functionality::SpotPrice, payload).unwrap().build().unwrap();
+
+        assert_eq!("https://api.coinbase.com/v2/prices/BTC-USD/spot", request.url().as_str());
+    }
+
+    #[test]
+    fn parse_errors() {
+        use ccxt::errors::Error;
+        use reqwest::StatusCode;
+        let c = Coinbase::new("coinbase", String::new(), String::new());
+        let e = c.parse_error("accounts", StatusCode::UNAUTHORIZED, r#"{"errors":[{"id":"expired_token","message":"The access token expired"}]}"#);
+        assert!(matches!(&e, Error::AuthenticationError(m) if m.endpoint == "accounts" && m.message == "The access token expired"));
+        let e = c.parse_error("accounts", StatusCode::FORBIDDEN, r#"{"errors":[{"id":"unknown_id","message":"no"}]}"#);
+        assert!(matches!(e, Error::PermissionDenied(_)));
+        assert!(matches!(c.parse_error("time", StatusCode::BAD_GATEWAY, "<html>"), Error::ExchangeNotAvailable(_)));
+        assert!(matches!(c.parse_error("time", StatusCode::NOT_FOUND, "{}"), Error::ApiCallError(_)));
+    }
+
+    #[test]
+    fn private_request_is_signed() {
+        let c = Coinbase::new("coinbase", "key".to_string(), "secret".to_string());
+        assert_eq!("54cea7571827389218b03a96308f02f2065699d9678d0203ea7cbfe49bb9e3f0",
+            c.get_signature("1616336594", "GET", "/v2/accounts?limit=100", ""));
+
+        let mut payload = HashMap::new();
+        payload.insert("limit", "100".to_string());
+        let request = c.get_request(&Functionality::Accounts, payload).unwrap().build().unwrap();
+        assert_eq!("https://api.coinbase.com/v2/accounts?limit=100", request.url().as_str());
+        let headers = request.headers();
+        let timestamp = headers["CB-ACCESS-TIMESTAMP"].to_str().unwrap();
+        assert_eq!(headers["CB-ACCESS-KEY"], "key");
+        assert_eq!(headers["CB-VERSION"], "2018-05-30");
+        assert_eq!(headers["CB-ACCESS-SIGN"].to_str().unwrap(), c.get_signature(timestamp, "GET", "/v2/accounts?limit=100", ""));
+
+        let anonymous = Coinbase::new("coinbase", String::new(), String::new());
+        assert!(anonymous.get_request(&Functionality::Accounts, HashMap::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn trading_fees_are_not_supported() {
+        let c = Coinbase::new("coinbase", String::new(), String::new());
+        assert!(matches!(c.fetch_trading_fees(&[]).await, Err(ccxt::errors::Error::ApiFunctionNotSupported(_))));
+    }
+}
@@ -0,0 +1,63 @@
+#![allow(dead_code)]
+
+use ccxt::coinbase::Coinbase;
+use ccxt::coinbase_advanced::CoinbaseAdvanced;
+use ccxt::kraken::Kraken;
+use ccxt::rate_limiter::RateLimiter;
+use ccxt::retry::RetryPolicy;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Answers the n-th request with the n-th (status, body), repeating the last one.
+/// Returns the url and the raw requests received so far.
+pub async fn serve(responses: Vec<(u16, &'static str)>) -> (&'static str, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url: &'static str = Box::leak(format!("http://{}", listener.local_addr().unwrap()).into_boxed_str());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let received = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 8192];
+            let len = socket.read(&mut buf).await.unwrap();
+            let n = {
+                let mut received = received.lock().unwrap();
+                received.push(String::from_utf8_lossy(&buf[..len]).to_string());
+                received.len() - 1
+            };
+            let (status, body) = responses[n.min(responses.len() - 1)];
+            let response = format!("HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    (url, requests)
+}
+
+/// Kraken client calling `url` without throttling public calls.
+pub fn kraken(url: &'static str) -> Kraken {
+    let mut k = Kraken::new("kraken", "key".to_string(), "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==".to_string())
+        .retry_policy(RetryPolicy::new(3, Duration::from_millis(1), Duration::from_millis(5)));
+    k.api.url = url;
+    k.rate_limiter = RateLimiter::from_millis(None);
+    k
+}
+
+/// Coinbase Advanced client calling `url` without throttling.
+pub fn coinbase_advanced(url: &'static str) -> CoinbaseAdvanced {
+    let mut c = CoinbaseAdvanced::new("coinbaseadvanced", "key".to_string(), "secret".to_string()).unwrap()
+        .retry_policy(RetryPolicy::none());
+    c.api.url = url;
+    c.rate_limiter = RateLimiter::from_millis(None);
+    c
+}
+
+/// Coinbase client calling `url` without throttling.
+pub fn coinbase(url: &'static str) -> Coinbase {
+    let mut c = Coinbase::new("coinbase", "key".to_string(), "secret".to_string())
+        .retry_policy(RetryPolicy::none());
+    c.api.url = url;
+    c.rate_limiter = RateLimiter::from_millis(None);
+    c
+}
@@ -1,8 +1,21 @@
 
+mod common;
+
 mod kraken_tests
 {
     use ccxt::kraken::*;
     use ccxt::exchange::*;
+    use std::collections::{BTreeMap, HashMap};
+    use std::str::FromStr;
+    use ccxt::Decimal;
+
+    fn kraken() -> Kraken {
+        Kraken::new("kraken", "".to_string(), "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==".to_string())
+    }
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
 
     #[test]
     /*
@@ -24,4 +37,741 @@ API-Sign 	4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+UClSV5n9E6aSS8MPtnRfp32bA
         let s = k.get_signature(&uri_path, &post_data, &nonce);
         assert_eq!("4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+UClSV5n9E6aSS8MPtnRfp32bAb0nmbRn6H8ndwLUQ==".to_string(), s);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parse_asset_pairs() {
+        let json = r#"{
+            "XXBTZUSD": {
+                "altname": "XBTUSD", "wsname": "XBT/USD", "aclass_base": "currency", "base": "XXBT",
+                "aclass_quote": "currency", "quote": "ZUSD", "lot": "unit", "cost_decimals": 5,
+                "pair_decimals": 1, "lot_decimals": 8, "lot_multiplier": 1,
+                "leverage_buy": [2, 3, 4, 5], "leverage_sell": [2, 3, 4, 5],
+                "fees": [[0, 0.26], [50000, 0.24]], "fees_maker": [[0, 0.16], [50000, 0.14]],
+                "fee_volume_currency": "ZUSD", "margin_call": 80, "margin_stop": 40,
+                "ordermin": "0.0001", "costmin": "0.5", "tick_size": "0.1", "status": "online"
+            },
+            "XETHXXBT.d": { "altname": "ETHXBT.d", "base": "XETH", "quote": "XXBT" }
+        }"#;
+        let pairs: BTreeMap<String, AssetPair> = serde_json::from_str(json).unwrap();
+        let markets = kraken().parse_markets(pairs);
+        assert_eq!(1, markets.len());
+
+        let m = &markets[0];
+        assert_eq!("XXBTZUSD", m.id);
+        assert_eq!("BTC/USD", m.symbol);
+        assert_eq!("BTC", m.base);
+        assert_eq!("XXBT", m.base_id);
+        assert_eq!("ZUSD", m.quote_id);
+        assert_eq!(Some(true), m.active);
+        assert_eq!(Some(1), m.precision.price);
+        assert_eq!(Some(8), m.precision.amount);
+        assert_eq!(Some(dec("0.0001")), m.limits.amount.min);
+        assert_eq!(Some(dec("5")), m.limits.leverage.max);
+        assert_eq!(Some(dec("0.0026")), m.taker);
+        assert_eq!(Some(dec("0.0016")), m.maker);
+        assert_eq!(FeeTier { volume: dec("50000"), fee: dec("0.0024") }, m.taker_tiers[1]);
+    }
+
+    #[test]
+    fn currency_codes() {
+        let k = kraken();
+        assert_eq!("BTC", k.currency_code("XXBT"));
+        assert_eq!("BTC", k.currency_code("XBT"));
+        assert_eq!("USD", k.currency_code("ZUSD"));
+        assert_eq!("DOGE", k.currency_code("XDG"));
+        assert_eq!("DOGE", k.currency_code("XXDG"));
+        assert_eq!("BTC.M", k.currency_code("XBT.M"));
+        assert_eq!("DOT.S", k.currency_code("DOT.S"));
+        assert_eq!("ETH2.S", k.currency_code("ETH2.S"));
+        assert_eq!("XTZ", k.currency_code("XTZ"));
+
+        assert_eq!("XBT", k.currency_id("BTC"));
+        assert_eq!("XDG", k.currency_id("DOGE"));
+        assert_eq!("XBT.F", k.currency_id("BTC.F"));
+        assert_eq!("USD", k.currency_id("USD"));
+    }
+
+    #[test]
+    fn symbols_without_markets() {
+        let k = kraken();
+        assert_eq!("BTC/USD", k.symbol("XXBTZUSD"));
+        assert_eq!("BTC/USD", k.symbol("XBTUSD"));
+        assert_eq!("DOT/USDT", k.symbol("DOTUSDT"));
+        assert_eq!("XBTUSD", k.market_id("BTC/USD").unwrap());
+        assert!(k.market_id("BTCUSD").is_err());
+    }
+
+    #[test]
+    fn symbols_with_markets() {
+        let k = kraken();
+        let json = r#"{"XETHZEUR": {"altname": "ETHEUR", "wsname": "ETH/EUR", "base": "XETH", "quote": "ZEUR"}}"#;
+        let pairs: BTreeMap<String, AssetPair> = serde_json::from_str(json).unwrap();
+        *k.market_cache().write().unwrap() = MarketCache::new(k.parse_markets(pairs));
+
+        assert_eq!("XETHZEUR", k.market_id("ETH/EUR").unwrap());
+        assert_eq!("ETH/EUR", k.symbol("XETHZEUR"));
+    }
+
+    #[test]
+    fn parse_balance() {
+        let balances: BTreeMap<String, ExtendedBalance> = serde_json::from_str(r#"{
+            "ZUSD": {"balance": "171288.6158", "hold_trade": "7.0354"},
+            "ZEUR": {"balance": "504861.8946", "hold_trade": "0.0000"},
+            "XXBT": {"balance": "1011.1908877900", "hold_trade": "0.5"},
+            "XBT.M": {"balance": "1.5"},
+            "DOT.S": {"balance": "10.0", "hold_trade": "0.0"}}"#).unwrap();
+
+        let b = kraken().parse_balance(balances);
+        assert_eq!(5, b.balances.len());
+        assert_eq!(CurrencyBalance { free: dec("171281.5804"), used: dec("7.0354"), total: dec("171288.6158") }, *b.get("USD").unwrap());
+        assert_eq!(dec("1010.69088779"), b.get("BTC").unwrap().free);
+        assert_eq!(dec("0.5"), b.get("BTC").unwrap().used);
+        assert_eq!(Decimal::ZERO, b.get("EUR").unwrap().used);
+        assert_eq!(dec("1.5"), b.get("BTC.M").unwrap().total);
+        assert_eq!(dec("10"), b.get("DOT.S").unwrap().total);
+        assert!(b.get("CHF").is_none());
+    }
+
+    #[test]
+    fn parse_ticker() {
+        let json = r#"{
+            "a": ["52609.60000", "1", "1.000"], "b": ["52609.50000", "1", "1.000"],
+            "c": ["52641.10000", "0.00080000"], "v": ["1920.83610601", "7954.00219674"],
+            "p": ["52389.94668", "54022.90683"], "t": [23329, 80463],
+            "l": ["51513.90000", "51513.90000"], "h": ["53219.90000", "57200.00000"], "o": "52280.40000"
+        }"#;
+        let info: TickerInfo = serde_json::from_str(json).unwrap();
+        let t = kraken().parse_ticker("XXBTZUSD", &info);
+
+        assert_eq!("BTC/USD", t.symbol);
+        assert_eq!(Some(dec("52609.5")), t.bid);
+        assert_eq!(Some(dec("52609.6")), t.ask);
+        assert_eq!(Some(dec("52641.1")), t.last);
+        assert_eq!(Some(dec("57200")), t.high);
+        assert_eq!(Some(dec("51513.9")), t.low);
+        assert_eq!(Some(dec("54022.90683")), t.vwap);
+        assert_eq!(Some(dec("7954.00219674")), t.base_volume);
+        assert_eq!(Some(dec("54022.90683") * dec("7954.00219674")), t.quote_volume);
+        assert_eq!(Some(dec("360.7")), t.change);
+        assert_eq!(dec("0.69"), t.percentage.unwrap().round_dp(2));
+    }
+
+    #[test]
+    fn public_request_uses_query_string() {
+        let k = kraken();
+        let mut payload = HashMap::new();
+        payload.insert("pair", format!("{},{}", k.market_id("BTC/USD").unwrap(), k.market_id("ETH/EUR").unwrap()));
+        let request = k.get_request(&Functionality::Ticker, payload).unwrap().build().unwrap();
+
+        assert_eq!("GET", request.method().as_str());
+        assert_eq!("https://api.kraken.com/0/public/Ticker?pair=XBTUSD%2CETHEUR", request.url().as_str());
+        assert!(request.body().is_none());
+    }
+
+    #[test]
+    fn parse_order_book() {
+        let json = r#"{
+            "asks": [["52527.10000", "0.010", 1616663113], ["52523.00000", "1.199", 1616663113]],
+            "bids": [["52522.80000", "0.753", 1616663112], ["52522.90000", "0.200", 1616663115]]
+        }"#;
+        let depth: Depth = serde_json::from_str(json).unwrap();
+        let book = kraken().parse_order_book("XXBTZUSD", &depth);
+
+        assert_eq!("BTC/USD", book.symbol);
+        assert_eq!(OrderBookLevel { price: dec("52523.00000"), amount: dec("1.199") }, *book.best_ask().unwrap());
+        assert_eq!(OrderBookLevel { price: dec("52522.9"), amount: dec("0.2") }, *book.best_bid().unwrap());
+        assert_eq!(dec("52527.1"), book.asks[1].price);
+        assert_eq!(1616663115, book.timestamp.unwrap().timestamp());
+        assert_eq!(None, book.nonce);
+    }
+
+    #[test]
+    fn parse_ohlc() {
+        let json = r#"{
+            "XXBTZUSD": [
+                [1616662740, "52591.9", "52599.9", "52591.8", "52599.9", "52599.1", "0.11091626", 5],
+                [1616662800, "52600.0", "52674.9", "52599.9", "52665.2", "52643.3", "2.49035996", 30]
+            ],
+            "last": 1616662740
+        }"#;
+        let data: OhlcData = serde_json::from_str(json).unwrap();
+        assert_eq!(1616662740, data.last);
+
+        let candles = kraken().parse_ohlcv(&data.pairs["XXBTZUSD"]);
+        assert_eq!(2, candles.len());
+        assert_eq!(1616662800, candles[1].timestamp.timestamp());
+        assert_eq!(dec("52600.0"), candles[1].open);
+        assert_eq!(dec("52674.9"), candles[1].high);
+        assert_eq!(dec("52599.9"), candles[1].low);
+        assert_eq!(dec("52665.2"), candles[1].close);
+        assert_eq!(dec("2.49035996"), candles[1].volume);
+    }
+
+    #[test]
+    fn ohlc_intervals() {
+        assert_eq!(1, Kraken::ohlc_interval(Timeframe::Minute1));
+        assert_eq!(240, Kraken::ohlc_interval(Timeframe::Hour4));
+        assert_eq!(10080, Kraken::ohlc_interval(Timeframe::Week1));
+        assert_eq!(21600, Kraken::ohlc_interval(Timeframe::Day15));
+        assert_eq!("15d", Timeframe::Day15.as_str());
+    }
+
+    #[test]
+    fn parse_trades() {
+        let json = r#"{
+            "XXBTZUSD": [
+                ["52609.50000", "0.00100000", 1616663618.3449, "s", "l", "", 7813],
+                ["52609.60000", "0.20000000", 1616663620.5, "b", "m", ""]
+            ],
+            "last": "1616663620500000000"
+        }"#;
+        let data: TradesData = serde_json::from_str(json).unwrap();
+        assert_eq!("1616663620500000000", data.last);
+
+        let k = kraken();
+        let entries = &data.pairs["XXBTZUSD"];
+        let sell = k.parse_trade("XXBTZUSD", &entries[0]).unwrap();
+        assert_eq!(Some("7813".to_string()), sell.id);
+        assert_eq!("BTC/USD", sell.symbol);
+        assert_eq!(Side::Sell, sell.side);
+        assert_eq!(Some(OrderType::Limit), sell.order_type);
+        assert_eq!(dec("52.6095"), sell.cost);
+        assert_eq!(1616663618344900000, sell.timestamp.timestamp_nanos_opt().unwrap());
+
+        let buy = k.parse_trade("XXBTZUSD", &entries[1]).unwrap();
+        assert_eq!(None, buy.id);
+        assert_eq!(Side::Buy, buy.side);
+        assert_eq!(Some(OrderType::Market), buy.order_type);
+    }
+
+    #[test]
+    fn parse_spreads() {
+        let json = r#"{"XXBTZUSD": [[1616663113, "52522.90000", "52523.00000"]], "last": 1616663113}"#;
+        let data: SpreadData = serde_json::from_str(json).unwrap();
+        let spread = kraken().parse_spread("XXBTZUSD", &data.pairs["XXBTZUSD"][0]).unwrap();
+        assert_eq!("BTC/USD", spread.symbol);
+        assert_eq!(dec("52522.9"), spread.bid);
+        assert_eq!(dec("52523"), spread.ask);
+        assert_eq!(1616663113, spread.timestamp.timestamp());
+    }
+
+    #[test]
+    fn limit_order_payload() {
+        let request = OrderRequest::limit("BTC/USD", Side::Buy, dec("1.25"), dec("37500"))
+            .post_only(true)
+            .time_in_force(TimeInForce::GoodTillCancel)
+            .client_order_id("my-order-1".to_string())
+            .validate(true);
+        let payload = kraken().order_payload(&request).unwrap();
+
+        assert_eq!("XBTUSD", payload["pair"]);
+        assert_eq!("buy", payload["type"]);
+        assert_eq!("limit", payload["ordertype"]);
+        assert_eq!("1.25", payload["volume"]);
+        assert_eq!("37500", payload["price"]);
+        assert_eq!("post", payload["oflags"]);
+        assert_eq!("GTC", payload["timeinforce"]);
+        assert_eq!("my-order-1", payload["cl_ord_id"]);
+        assert_eq!("true", payload["validate"]);
+    }
+
+    #[test]
+    fn stop_loss_limit_order_payload() {
+        let k = kraken();
+        let request = OrderRequest::new("ETH/EUR", Side::Sell, OrderType::StopLossLimit, dec("2"))
+            .stop_price(dec("1800"))
+            .price(dec("1790.5"));
+        let payload = k.order_payload(&request).unwrap();
+        assert_eq!("stop-loss-limit", payload["ordertype"]);
+        assert_eq!("sell", payload["type"]);
+        assert_eq!("1800", payload["price"]);
+        assert_eq!("1790.5", payload["price2"]);
+        assert!(!payload.contains_key("validate"));
+
+        let missing_stop = OrderRequest::new("ETH/EUR", Side::Sell, OrderType::TakeProfit, dec("2"));
+        assert!(k.order_payload(&missing_stop).is_err());
+    }
+
+    #[test]
+    fn order_payload_uses_market_precision() {
+        let k = kraken();
+        let json = r#"{"XXBTZUSD": {"altname": "XBTUSD", "wsname": "XBT/USD", "base": "XXBT", "quote": "ZUSD", "pair_decimals": 1, "lot_decimals": 8}}"#;
+        let pairs: BTreeMap<String, AssetPair> = serde_json::from_str(json).unwrap();
+        *k.market_cache().write().unwrap() = MarketCache::new(k.parse_markets(pairs));
+
+        let request = OrderRequest::limit("BTC/USD", Side::Buy, dec("0.123456789"), dec("37500.06"));
+        let payload = k.order_payload(&request).unwrap();
+        assert_eq!("XXBTZUSD", payload["pair"]);
+        assert_eq!("0.12345678", payload["volume"]);
+        assert_eq!("37500.1", payload["price"]);
+    }
+
+    #[test]
+    fn private_request_body_is_signed_post_data() {
+        let k = kraken();
+        let mut payload = HashMap::new();
+        payload.insert("pair", "XBTUSD".to_string());
+        payload.insert("cl_ord_id", "a,b+c".to_string());
+        let request = k.get_request(&Functionality::AddOrder, payload).unwrap().build().unwrap();
+
+        let body = String::from_utf8(request.body().unwrap().as_bytes().unwrap().to_vec()).unwrap();
+        assert!(body.contains("cl_ord_id=a%2Cb%2Bc"));
+        let nonce = body.split('&')
+            .find_map(|p| p.strip_prefix("nonce="))
+            .unwrap()
+            .to_string();
+        let signature = k.get_signature(&"/0/private/AddOrder".to_string(), &body, &nonce);
+        assert_eq!(signature, request.headers()["API-Sign"].to_str().unwrap());
+    }
+
+    #[test]
+    fn parse_add_order() {
+        let json = r#"{"descr": {"order": "buy 1.25000000 XBTUSD @ limit 27500.0"}, "txid": ["OU22CG-KLAF2-FWUDD7"]}"#;
+        let result: AddOrderResult = serde_json::from_str(json).unwrap();
+        let request = OrderRequest::limit("BTC/USD", Side::Buy, dec("1.25"), dec("27500"));
+        let order = kraken().parse_add_order(&request, &result);
+
+        assert_eq!("OU22CG-KLAF2-FWUDD7", order.id);
+        assert_eq!(OrderStatus::Open, order.status);
+        assert_eq!(dec("1.25"), order.remaining);
+        assert_eq!(Some("buy 1.25000000 XBTUSD @ limit 27500.0".to_string()), order.description);
+
+        let validated: AddOrderResult = serde_json::from_str(r#"{"descr": {"order": "buy 1.25000000 XBTUSD @ limit 27500.0"}}"#).unwrap();
+        let order = kraken().parse_add_order(&request.validate(true), &validated);
+        assert_eq!("", order.id);
+        assert_eq!(OrderStatus::Pending, order.status);
+    }
+
+    #[test]
+    fn parse_orders() {
+        let json = r#"{
+            "open": {
+                "OQCLML-BW3P3-BUCMWZ": {
+                    "refid": null, "userref": 0, "status": "open", "opentm": 1616666559.8974, "starttm": 0, "expiretm": 0,
+                    "descr": {"pair": "XBTUSD", "type": "buy", "ordertype": "limit", "price": "30010.0", "price2": "0",
+                              "leverage": "none", "order": "buy 1.25000000 XBTUSD @ limit 30010.0", "close": ""},
+                    "vol": "1.25000000", "vol_exec": "0.37500000", "cost": "11253.7", "fee": "0.00000", "price": "30010.0",
+                    "stopprice": "0.00000", "limitprice": "0.00000", "misc": "", "oflags": "fciq,post",
+                    "trades": ["TCCCTY-WE2O6-P3NB37"]
+                },
+                "OB5VMB-B4U2U-DK2WRW": {
+                    "refid": null, "userref": 120, "status": "pending", "opentm": 1616665899.5, "starttm": 0, "expiretm": 1616752299,
+                    "descr": {"pair": "ETHEUR", "type": "sell", "ordertype": "stop-loss-limit", "price": "1800.0", "price2": "1790.5",
+                              "leverage": "none", "order": "sell 2.00000000 ETHEUR @ stop loss 1800.0 -> limit 1790.5", "close": ""},
+                    "vol": "2.00000000", "vol_exec": "0.00000000", "cost": "0.00000", "fee": "0.00000", "price": "0.00000",
+                    "misc": "", "oflags": "fciq"
+                }
+            }
+        }"#;
+        let result: OpenOrdersResult = serde_json::from_str(json).unwrap();
+        let k = kraken();
+
+        let limit = k.parse_order("OQCLML-BW3P3-BUCMWZ", &result.open["OQCLML-BW3P3-BUCMWZ"]);
+        assert_eq!("BTC/USD", limit.symbol);
+        assert_eq!(OrderStatus::Open, limit.status);
+        assert_eq!(OrderType::Limit, limit.order_type);
+        assert_eq!(Side::Buy, limit.side);
+        assert!(limit.post_only);
+        assert_eq!(Some(dec("30010")), limit.price);
+        assert_eq!(dec("0.875"), limit.remaining);
+        assert_eq!(Some(dec("30010")), limit.average);
+        assert_eq!(None, limit.client_order_id);
+        assert_eq!(None, limit.time_in_force);
+
+        let stop = k.parse_order("OB5VMB-B4U2U-DK2WRW", &result.open["OB5VMB-B4U2U-DK2WRW"]);
+        assert_eq!("ETH/EUR", stop.symbol);
+        assert_eq!(OrderStatus::Pending, stop.status);
+        assert_eq!(OrderType::StopLossLimit, stop.order_type);
+        assert_eq!(Side::Sell, stop.side);
+        assert_eq!(Some(dec("1790.5")), stop.price);
+        assert_eq!(Some(dec("1800")), stop.stop_price);
+        assert_eq!(None, stop.average);
+        assert_eq!(Some("120".to_string()), stop.client_order_id);
+        assert_eq!(1616752299, match stop.time_in_force { Some(TimeInForce::GoodTillDate(t)) => t.timestamp(), _ => 0 });
+    }
+
+    #[test]
+    fn parse_trades_history() {
+        let json = r#"{
+            "trades": {
+                "THVRQM-33VKH-UCI7BS": {
+                    "ordertxid": "OQCLML-BW3P3-BUCMWZ", "postxid": "TKH2SE-M7IF5-CFI7LT", "pair": "XXBTZUSD",
+                    "time": 1616667796.8802, "type": "buy", "ordertype": "limit", "price": "30010.00000",
+                    "cost": "600.20000", "fee": "0.96032", "vol": "0.02000000", "margin": "0.00000", "misc": "", "maker": true
+                },
+                "TCWJEG-FL4SZ-3FKGH6": {
+                    "ordertxid": "OQCLML-BW3P3-BUCMWZ", "postxid": "TKH2SE-M7IF5-CFI7LT", "pair": "XETHZEUR",
+                    "time": 1616667769.6396, "type": "sell", "ordertype": "market", "price": "1800.00000",
+                    "cost": "3600.00000", "fee": "9.36000", "vol": "2.00000000", "margin": "0.00000", "misc": ""
+                }
+            },
+            "count": 2
+        }"#;
+        let result: TradesHistoryResult = serde_json::from_str(json).unwrap();
+        assert_eq!(2, result.count);
+
+        let k = kraken();
+        let buy = k.parse_my_trade("THVRQM-33VKH-UCI7BS", &result.trades["THVRQM-33VKH-UCI7BS"]).unwrap();
+        assert_eq!(Some("THVRQM-33VKH-UCI7BS".to_string()), buy.id);
+        assert_eq!("BTC/USD", buy.symbol);
+        assert_eq!(Some("OQCLML-BW3P3-BUCMWZ".to_string()), buy.order_id);
+        assert_eq!(Side::Buy, buy.side);
+        assert_eq!(dec("600.2"), buy.cost);
+        assert_eq!(Some(dec("0.96032")), buy.fee);
+        assert_eq!(Some("USD".to_string()), buy.fee_currency);
+        assert_eq!(Some(TakerOrMaker::Maker), buy.taker_or_maker);
+
+        let sell = k.parse_my_trade("TCWJEG-FL4SZ-3FKGH6", &result.trades["TCWJEG-FL4SZ-3FKGH6"]).unwrap();
+        assert_eq!("ETH/EUR", sell.symbol);
+        assert_eq!(Side::Sell, sell.side);
+        assert_eq!(Some(OrderType::Market), sell.order_type);
+        assert_eq!(Some("EUR".to_string()), sell.fee_currency);
+        assert_eq!(None, sell.taker_or_maker);
+    }
+
+    #[test]
+    fn parse_ledgers() {
+        let json = r#"{
+            "ledger": {
+                "L4UESK-KG3EQ-UFO4T5": {
+                    "refid": "TJKLXX-PGMUI-4NTLXU", "time": 1610464484.1787, "type": "trade", "subtype": "",
+                    "aclass": "currency", "asset": "ZGBP", "amount": "-24.5000", "fee": "0.0490", "balance": "459567.9171"
+                },
+                "LMKZCZ-Z3GVL-CXKK4H": {
+                    "refid": "QCCBZ4-QYXHZN-3UFGYX", "time": 1610444484.1787, "type": "staking", "subtype": "",
+                    "aclass": "currency", "asset": "DOT.S", "amount": "0.0120", "fee": "0.0000", "balance": "10.0120"
+                },
+                "LXKD3Z-7KQUM-BVEZOW": {
+                    "refid": "", "time": 1610454484.5, "type": "adjustment", "subtype": "",
+                    "aclass": "currency", "asset": "XXBT", "amount": "0.0001", "fee": "0.0000", "balance": "1.0001"
+                }
+            },
+            "count": 3
+        }"#;
+        let result: LedgersResult = serde_json::from_str(json).unwrap();
+        assert_eq!(3, result.count);
+
+        let k = kraken();
+        let trade = k.parse_ledger_entry("L4UESK-KG3EQ-UFO4T5", &result.ledger["L4UESK-KG3EQ-UFO4T5"]).unwrap();
+        assert_eq!(LedgerEntryType::Trade, trade.entry_type);
+        assert_eq!(Some("TJKLXX-PGMUI-4NTLXU".to_string()), trade.reference_id);
+        assert_eq!("GBP", trade.currency);
+        assert_eq!(dec("-24.5"), trade.amount);
+        assert_eq!(dec("0.049"), trade.fee);
+        assert_eq!(Some(dec("459567.9171")), trade.balance);
+
+        let staking = k.parse_ledger_entry("LMKZCZ-Z3GVL-CXKK4H", &result.ledger["LMKZCZ-Z3GVL-CXKK4H"]).unwrap();
+        assert_eq!(LedgerEntryType::Staking, staking.entry_type);
+        assert_eq!("DOT.S", staking.currency);
+
+        let other = k.parse_ledger_entry("LXKD3Z-7KQUM-BVEZOW", &result.ledger["LXKD3Z-7KQUM-BVEZOW"]).unwrap();
+        assert_eq!(LedgerEntryType::Other("adjustment".to_string()), other.entry_type);
+        assert_eq!(None, other.reference_id);
+        assert_eq!("BTC", other.currency);
+    }
+
+    #[test]
+    fn parse_deposit_methods_and_addresses() {
+        let k = kraken();
+        let methods: Vec<DepositMethodInfo> = serde_json::from_str(r#"[
+            {"method": "Bitcoin", "limit": false, "fee": "0.0000000000", "gen-address": true},
+            {"method": "Bitcoin Lightning", "limit": "0.05", "fee": "0.00000000", "address-setup-fee": "0.00", "gen-address": false}
+        ]"#).unwrap();
+        let bitcoin = k.parse_deposit_method(&methods[0]);
+        assert_eq!("Bitcoin", bitcoin.method);
+        assert_eq!(None, bitcoin.limit);
+        assert!(bitcoin.can_generate_address);
+        let lightning = k.parse_deposit_method(&methods[1]);
+        assert_eq!(Some(dec("0.05")), lightning.limit);
+        assert!(!lightning.can_generate_address);
+
+        let addresses: Vec<DepositAddressInfo> = serde_json::from_str(r#"[
+            {"address": "2N9fRkx5JTWXWHmXzZtvhQsufvoYRMq9ExV", "expiretm": "0", "new": true},
+            {"address": "rLHzPsX6oXkzU2qL12kHCH8G8cnZv1rBJh", "expiretm": "0", "tag": "1361101127"}
+        ]"#).unwrap();
+        let address = k.parse_deposit_address("BTC", "Bitcoin", &addresses[0]);
+        assert_eq!("2N9fRkx5JTWXWHmXzZtvhQsufvoYRMq9ExV", address.address);
+        assert!(address.new);
+        assert_eq!(None, address.expires);
+        assert_eq!(Some("1361101127".to_string()), k.parse_deposit_address("XRP", "Ripple XRP", &addresses[1]).tag);
+    }
+
+    #[test]
+    fn parse_deposit_status() {
+        let infos: Vec<TransactionInfo> = serde_json::from_str(r#"[
+            {"method": "Bitcoin", "aclass": "currency", "asset": "XXBT", "refid": "QSKHCQL-4GRPH-AYKPTB",
+             "txid": "ec6bb66b7e1baa4ba6ccf7d7fe4e5d22d5ac6be84a0a3af0c7f0d7b1b5e5c2e0", "info": "2N9fRkx5JTWXWHmXzZtvhQsufvoYRMq9ExV",
+             "amount": "0.78125000", "fee": "0.0000000000", "time": 1617014586, "status": "Success"},
+            {"method": "Ether (Hex)", "aclass": "currency", "asset": "XETH", "refid": "QHCXNQ2-UPHEQ-ZDVRBQ",
+             "txid": "", "info": "", "amount": "1.000", "fee": "0.0", "time": 1617015586, "status": "Pending", "status-prop": "onhold"}
+        ]"#).unwrap();
+        let k = kraken();
+        let done = k.parse_transaction(TransactionType::Deposit, &infos[0]);
+        assert_eq!("QSKHCQL-4GRPH-AYKPTB", done.id);
+        assert_eq!("BTC", done.currency);
+        assert_eq!(TransactionStatus::Ok, done.status);
+        assert_eq!(dec("0.78125"), done.amount);
+        assert_eq!(1617014586, done.timestamp.unwrap().timestamp());
+
+        let pending = k.parse_transaction(TransactionType::Deposit, &infos[1]);
+        assert_eq!(TransactionStatus::Pending, pending.status);
+        assert_eq!(None, pending.txid);
+        assert_eq!(None, pending.address);
+    }
+
+    #[test]
+    fn withdrawal_key_allow_list() {
+        let cold_storage = WithdrawalDestination::Key("cold storage".to_string());
+        let unknown = WithdrawalDestination::Key("someone else".to_string());
+        let address = WithdrawalDestination::Address { address: "2N9fRkx5JTWXWHmXzZtvhQsufvoYRMq9ExV".to_string(), tag: None };
+
+        let unrestricted = kraken();
+        assert_eq!("someone else", unrestricted.withdrawal_key(&unknown).unwrap());
+        assert!(unrestricted.withdrawal_key(&address).is_err());
+
+        let restricted = kraken().allowed_withdrawal_keys(&["cold storage"]);
+        assert_eq!("cold storage", restricted.withdrawal_key(&cold_storage).unwrap());
+        assert!(matches!(restricted.withdrawal_key(&unknown), Err(ccxt::errors::Error::WithdrawalKeyNotAllowed(k)) if k == "someone else"));
+    }
+
+    #[test]
+    fn parse_withdraw_info() {
+        let info: WithdrawInfoResult = serde_json::from_str(
+            r#"{"method": "Bitcoin", "limit": "332.00956139", "amount": "0.72485000", "fee": "0.00015000"}"#).unwrap();
+        assert_eq!(Some(dec("332.00956139")), info.limit);
+        assert_eq!(dec("0.00015"), info.fee);
+    }
+
+    #[test]
+    fn parse_open_positions() {
+        let json = r#"{
+            "TF5GVO-T7ZZ2-6NBKBI": {
+                "ordertxid": "OLWNFG-LLH4R-D6SFFP", "posstatus": "open", "pair": "XXBTZUSD", "time": 1605280097.8294,
+                "type": "buy", "ordertype": "limit", "cost": "104610.52842", "fee": "289.06565", "vol": "8.82412861",
+                "vol_closed": "0.20200000", "margin": "20922.10568", "value": "258797.5", "net": "+154186.9728",
+                "terms": "0.0100% per 4 hours", "rollovertm": "1616672637", "misc": "", "oflags": ""
+            }
+        }"#;
+        let infos: BTreeMap<String, PositionInfo> = serde_json::from_str(json).unwrap();
+        let p = kraken().parse_position("TF5GVO-T7ZZ2-6NBKBI", &infos["TF5GVO-T7ZZ2-6NBKBI"]);
+
+        assert_eq!("BTC/USD", p.symbol);
+        assert_eq!(PositionSide::Long, p.side);
+        assert_eq!(dec("8.62212861"), p.size);
+        assert_eq!(dec("11855.05"), p.entry_price.unwrap().round_dp(2));
+        assert_eq!(Some(dec("154186.9728")), p.unrealized_pnl);
+        assert_eq!(Some(dec("258797.5")), p.value);
+        assert_eq!(dec("20922.10568"), p.margin);
+        assert_eq!(dec("289.06565"), p.fee);
+        assert_eq!(1616672637, p.rollover_time.unwrap().timestamp());
+        assert_eq!(Some("0.0100% per 4 hours".to_string()), p.rollover_terms);
+    }
+
+    #[test]
+    fn parse_trade_volume() {
+        let json = r#"{
+            "currency": "ZUSD", "volume": "200709587.4223",
+            "fees": {"XXBTZUSD": {"fee": "0.1000", "minfee": "0.1000", "maxfee": "0.2600", "nextfee": null, "nextvolume": null, "tiervolume": "10000000.0000"}},
+            "fees_maker": {"XXBTZUSD": {"fee": "0.0000", "minfee": "0.0000", "maxfee": "0.1600", "nextfee": null, "nextvolume": null, "tiervolume": "10000000.0000"}}
+        }"#;
+        let volume: TradeVolume = serde_json::from_str(json).unwrap();
+        let fees = kraken().parse_trading_fees(&volume);
+
+        let btc = &fees["BTC/USD"];
+        assert_eq!(dec("0.001"), btc.taker);
+        assert_eq!(Decimal::ZERO, btc.maker);
+        assert_eq!(Some(dec("200709587.4223")), btc.volume);
+        assert_eq!(Some("USD".to_string()), btc.volume_currency);
+        assert_eq!(Some(dec("10000000")), btc.tier_volume);
+    }
+
+    #[test]
+    fn calculate_fees() {
+        let json = r#"{"XXBTZUSD": {"altname": "XBTUSD", "wsname": "XBT/USD", "base": "XXBT", "quote": "ZUSD",
+                        "fees": [[0, 0.26]], "fees_maker": [[0, 0.16]]}}"#;
+        let pairs: BTreeMap<String, AssetPair> = serde_json::from_str(json).unwrap();
+        let mut market = kraken().parse_markets(pairs).remove(0);
+
+        let taker = calculate_fee(&market, Side::Buy, dec("0.5"), dec("40000"), true).unwrap();
+        assert_eq!(Fee { currency: "USD".to_string(), rate: dec("0.0026"), cost: dec("52") }, taker);
+        let maker = calculate_fee(&market, Side::Sell, dec("0.5"), dec("40000"), false).unwrap();
+        assert_eq!(dec("32"), maker.cost);
+
+        market.fee_side = FeeSide::Get;
+        let buy = calculate_fee(&market, Side::Buy, dec("0.5"), dec("40000"), true).unwrap();
+        assert_eq!(Fee { currency: "BTC".to_string(), rate: dec("0.0026"), cost: dec("0.0013") }, buy);
+    }
+
+    #[test]
+    fn cancel_penalties() {
+        use std::time::Duration;
+        assert_eq!(cancel_penalty(Duration::from_millis(500)), 8);
+        assert_eq!(cancel_penalty(Duration::from_secs(12)), 5);
+        assert_eq!(cancel_penalty(Duration::from_secs(60)), 2);
+        assert_eq!(cancel_penalty(Duration::from_secs(300)), 0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        use ccxt::errors::Error;
+        let k = kraken();
+        let e = k.parse_error("AddOrder", "EOrder:Insufficient funds");
+        assert!(matches!(&e, Error::InsufficientFunds(m) if m.endpoint == "AddOrder" && m.message == "EOrder:Insufficient funds"));
+        assert!(matches!(k.parse_error("Balance", "EAPI:Invalid nonce"), Error::InvalidNonce(_)));
+        assert!(matches!(k.parse_error("QueryOrders", "EOrder:Unknown order"), Error::OrderNotFound(_)));
+        assert!(matches!(k.parse_error("AddOrder", "EService:Market in cancel_only mode"), Error::OnMaintenance(_)));
+        let e = k.parse_error("AddOrder", "EGeneral:Invalid arguments:volume");
+        assert!(matches!(&e, Error::ApiCallError(m) if m.endpoint == "AddOrder" && m.message == "EGeneral:Invalid arguments:volume"));
+        assert_eq!(Some("AddOrder"), e.api_message().map(|m| m.endpoint));
+    }
+
+    const NO_MARKETS: (u16, &str) = (200, r#"{"error":[],"result":{}}"#);
+
+    fn since() -> Option<ccxt::DateTime> {
+        Some(chrono::TimeZone::timestamp_opt(&chrono::Utc, 1000, 0).unwrap())
+    }
+
+    #[tokio::test]
+    async fn closed_orders_page_back_to_since() {
+        let pages = vec![
+            NO_MARKETS,
+            (200, r#"{"error":[],"result":{"count":3,"closed":{
+                "O3":{"status":"closed","opentm":1030,"closetm":1031,"descr":{"pair":"XBTUSD","type":"buy","ordertype":"market"},"vol":"1","vol_exec":"1"},
+                "O2":{"status":"closed","opentm":1020,"closetm":1021,"descr":{"pair":"XBTUSD","type":"buy","ordertype":"market"},"vol":"1","vol_exec":"1"}}}}"#),
+            (200, r#"{"error":[],"result":{"count":3,"closed":{
+                "O1":{"status":"closed","opentm":1010,"closetm":1011,"descr":{"pair":"XBTUSD","type":"buy","ordertype":"market"},"vol":"1","vol_exec":"1"}}}}"#),
+        ];
+        let (url, requests) = crate::common::serve(pages.clone()).await;
+        let orders = crate::common::kraken(url).fetch_closed_orders(None, since(), Some(2)).await.unwrap();
+        assert_eq!(vec!["O1", "O2"], orders.iter().map(|o| o.id.as_str()).collect::<Vec<_>>());
+        let sent = requests.lock().unwrap().clone();
+        assert_eq!(3, sent.len());
+        assert!(sent[1].contains("ofs=0") && sent[1].contains("start=1000"));
+        assert!(sent[2].contains("ofs=2"));
+
+        let (url, requests) = crate::common::serve(pages).await;
+        let orders = crate::common::kraken(url).fetch_closed_orders(None, None, Some(2)).await.unwrap();
+        assert_eq!(vec!["O2", "O3"], orders.iter().map(|o| o.id.as_str()).collect::<Vec<_>>());
+        assert_eq!(2, requests.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn my_trades_page_back_to_since() {
+        let pages = vec![
+            NO_MARKETS,
+            (200, r#"{"error":[],"result":{"count":3,"trades":{
+                "T3":{"ordertxid":"O3","pair":"XXBTZUSD","time":1030,"type":"buy","ordertype":"limit","price":"3","cost":"3","fee":"0","vol":"1"},
+                "T2":{"ordertxid":"O2","pair":"XXBTZUSD","time":1020,"type":"buy","ordertype":"limit","price":"2","cost":"2","fee":"0","vol":"1"}}}}"#),
+            (200, r#"{"error":[],"result":{"count":3,"trades":{
+                "T1":{"ordertxid":"O1","pair":"XXBTZUSD","time":1010,"type":"buy","ordertype":"limit","price":"1","cost":"1","fee":"0","vol":"1"}}}}"#),
+        ];
+        let (url, requests) = crate::common::serve(pages.clone()).await;
+        let trades = crate::common::kraken(url).fetch_my_trades(None, since(), Some(2)).await.unwrap();
+        assert_eq!(vec![Some("T1"), Some("T2")], trades.iter().map(|t| t.id.as_deref()).collect::<Vec<_>>());
+        let sent = requests.lock().unwrap().clone();
+        assert_eq!(3, sent.len());
+        assert!(sent[2].contains("ofs=2") && sent[2].contains("start=1000"));
+
+        let (url, _) = crate::common::serve(pages).await;
+        let trades = crate::common::kraken(url).fetch_my_trades(None, None, Some(2)).await.unwrap();
+        assert_eq!(vec![Some("T2"), Some("T3")], trades.iter().map(|t| t.id.as_deref()).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn ledger_pages_back_to_since() {
+        let pages = vec![
+            (200, r#"{"error":[],"result":{"count":3,"ledger":{
+                "L3":{"refid":"R3","time":1030,"type":"trade","asset":"ZUSD","amount":"3","fee":"0","balance":"6"},
+                "L2":{"refid":"R2","time":1020,"type":"trade","asset":"ZUSD","amount":"2","fee":"0","balance":"3"}}}}"#),
+            (200, r#"{"error":[],"result":{"count":3,"ledger":{
+                "L1":{"refid":"R1","time":1010,"type":"trade","asset":"ZUSD","amount":"1","fee":"0","balance":"1"}}}}"#),
+        ];
+        let (url, requests) = crate::common::serve(pages.clone()).await;
+        let entries = crate::common::kraken(url).fetch_ledger(None, since(), Some(2)).await.unwrap();
+        assert_eq!(vec!["L1", "L2"], entries.iter().map(|e| e.id.as_str()).collect::<Vec<_>>());
+        let sent = requests.lock().unwrap().clone();
+        assert_eq!(2, sent.len());
+        assert!(sent[1].contains("ofs=2") && sent[1].contains("start=1000"));
+
+        let (url, _) = crate::common::serve(pages).await;
+        let entries = crate::common::kraken(url).fetch_ledger(None, None, Some(2)).await.unwrap();
+        assert_eq!(vec!["L2", "L3"], entries.iter().map(|e| e.id.as_str()).collect::<Vec<_>>());
+    }
+
+    const TRANSACTIONS: (u16, &str) = (200, r#"{"error":[],"result":[
+        {"method":"Bitcoin","aclass":"currency","asset":"XXBT","refid":"R3","txid":"t3","info":"a","amount":"3","fee":"0","time":1030,"status":"Success"},
+        {"method":"Bitcoin","aclass":"currency","asset":"XXBT","refid":"R2","txid":"t2","info":"a","amount":"2","fee":"0","time":1020,"status":"Success"},
+        {"method":"Bitcoin","aclass":"currency","asset":"XXBT","refid":"R1","txid":"t1","info":"a","amount":"1","fee":"0","time":1010,"status":"Success"}]}"#);
+
+    #[tokio::test]
+    async fn deposits_start_at_since() {
+        let (url, _) = crate::common::serve(vec![TRANSACTIONS]).await;
+        let k = crate::common::kraken(url);
+        let ids = |t: Vec<Transaction>| t.into_iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(vec!["R1", "R2"], ids(k.fetch_deposits(None, since(), Some(2)).await.unwrap()));
+        assert_eq!(vec!["R2", "R3"], ids(k.fetch_deposits(None, None, Some(2)).await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn withdrawals_start_at_since() {
+        let (url, _) = crate::common::serve(vec![TRANSACTIONS]).await;
+        let k = crate::common::kraken(url);
+        let ids = |t: Vec<Transaction>| t.into_iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(vec!["R1", "R2"], ids(k.fetch_withdrawals(None, since(), Some(2)).await.unwrap()));
+        assert_eq!(vec!["R2", "R3"], ids(k.fetch_withdrawals(None, None, Some(2)).await.unwrap()));
+    }
+
+    const PYUSD_MARKETS: (u16, &str) = (200, r#"{"error":[],"result":{
+        "XXBTZPYUSD":{"altname":"XBTPYUSD","wsname":"XBT/PYUSD","base":"XXBT","quote":"PYUSD"}}}"#);
+
+    #[tokio::test]
+    async fn order_pairs_resolve_through_altnames() {
+        let (url, _) = crate::common::serve(vec![
+            PYUSD_MARKETS,
+            (200, r#"{"error":[],"result":{"open":{
+                "O1":{"status":"open","opentm":1010,"descr":{"pair":"XBTPYUSD","type":"buy","ordertype":"limit","price":"60000"},"vol":"1","vol_exec":"0"}}}}"#),
+        ]).await;
+        let k = crate::common::kraken(url);
+        let orders = k.fetch_open_orders(Some("BTC/PYUSD")).await.unwrap();
+        assert_eq!(1, orders.len());
+        assert_eq!("BTC/PYUSD", orders[0].symbol);
+        assert_eq!("BTC/PYUSD", k.symbol("XBT/PYUSD"));
+    }
+
+    #[tokio::test]
+    async fn unknown_orders_and_failed_cancels() {
+        use ccxt::errors::Error;
+        let (url, _) = crate::common::serve(vec![NO_MARKETS, (200, r#"{"error":[],"result":{}}"#)]).await;
+        let r = crate::common::kraken(url).fetch_order("OUNKNOWN").await;
+        assert!(matches!(r, Err(Error::OrderNotFound(_))));
+
+        let (url, requests) = crate::common::serve(vec![
+            NO_MARKETS,
+            (200, r#"{"error":[],"result":{"open":{
+                "O1":{"status":"open","opentm":1010,"descr":{"pair":"XBTUSD","type":"buy","ordertype":"market"},"vol":"1","vol_exec":"0"},
+                "O2":{"status":"open","opentm":1020,"descr":{"pair":"XBTUSD","type":"buy","ordertype":"market"},"vol":"1","vol_exec":"0"}}}}"#),
+            (200, r#"{"error":["EOrder:Unknown order"],"result":null}"#),
+            (200, r#"{"error":[],"result":{"count":1}}"#),
+        ]).await;
+        let results = crate::common::kraken(url).cancel_all_orders(None).await.unwrap();
+        assert_eq!(2, results.len());
+        assert!(matches!(results["O1"], Err(Error::OrderNotFound(_))));
+        assert!(results["O2"].is_ok());
+        assert_eq!(4, requests.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn query_calls_count_twice() {
+        let (url, requests) = crate::common::serve(vec![(200, r#"{"error":[],"result":{}}"#)]).await;
+        let k = crate::common::kraken(url).tier(VerificationTier::Pro);
+        let ids: Vec<String> = (0..200).map(|i| format!("L{}", i)).collect();
+        let ids: Vec<&str> = ids.iter().map(|i| i.as_str()).collect();
+        let start = std::time::Instant::now();
+        k.fetch_ledger_entries_by_ids(&ids).await.unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_millis(1000));
+        // the counter of the Pro tier is full after 10 queries, the next one waits until it decays by 2
+        k.fetch_my_trades_by_ids(&["T1"]).await.unwrap();
+        assert!(start.elapsed() >= std::time::Duration::from_millis(1500));
+        assert_eq!(12, requests.lock().unwrap().len());
+    }
+}
@@ -0,0 +1,200 @@
+mod kraken_ws_tests {
+    use ccxt::errors::Error;
+    use ccxt::exchange::Timeframe;
+    use ccxt::kraken::Kraken;
+    use ccxt::kraken_ws::{KrakenWs, STREAM_CAPACITY};
+    use ccxt::Decimal;
+    use futures::{SinkExt, StreamExt};
+    use std::str::FromStr;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite::Message;
+
+    const SYSTEM_STATUS: &str = r#"{"connectionID":8628615390848610000,"event":"systemStatus","status":"online","version":"1.9.0"}"#;
+
+    /// Sends `replies[0]` on connect and `replies[n]` after the n-th client message, then closes the connection.
+    /// Client messages are forwarded to the returned receiver.
+    async fn serve(replies: Vec<Vec<&'static str>>) -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            let mut replies = replies.into_iter();
+            for m in replies.next().unwrap_or_default() {
+                ws.send(Message::Text(m.to_string())).await.unwrap();
+            }
+            for batch in replies {
+                match ws.next().await {
+                    Some(Ok(Message::Text(text))) => tx.send(text).unwrap(),
+                    _ => return,
+                }
+                for m in batch {
+                    ws.send(Message::Text(m.to_string())).await.unwrap();
+                }
+            }
+            ws.close(None).await.unwrap();
+        });
+        (url, rx)
+    }
+
+    fn kraken() -> Kraken {
+        Kraken::new("kraken", String::new(), String::new())
+    }
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[tokio::test]
+    async fn ticker_and_trades_streams() {
+        let (url, mut requests) = serve(vec![
+            vec![SYSTEM_STATUS],
+            vec![
+                r#"{"channelID":340,"channelName":"ticker","event":"subscriptionStatus","pair":"XBT/USD","status":"subscribed","subscription":{"name":"ticker"}}"#,
+                r#"{"event":"heartbeat"}"#,
+                r#"[340,{"a":["5525.40000",1,"1.000"],"b":["5525.10000",1,"1.000"],"c":["5525.10000","0.00398963"],"v":["2634.11501494","3591.17907851"],"p":["5631.44067","5653.78939"],"t":[11493,16267],"l":["5505.00000","5505.00000"],"h":["5783.00000","5783.00000"],"o":["5760.70000","5763.40000"]},"ticker","XBT/USD"]"#,
+            ],
+            vec![
+                r#"[337,[["5541.20000","0.15850568","1534614057.321597","s","l",""],["6060.00000","0.02455000","1534614057.324998","b","l",""]],"trade","XBT/USD"]"#,
+            ],
+        ]).await;
+        let ws = KrakenWs::connect_to(&url, kraken()).await.unwrap();
+
+        let mut tickers = ws.watch_ticker(&["BTC/USD"]).await.unwrap();
+        let subscribe: serde_json::Value = serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+        assert_eq!("subscribe", subscribe["event"]);
+        assert_eq!("XBT/USD", subscribe["pair"][0]);
+        assert_eq!("ticker", subscribe["subscription"]["name"]);
+
+        let ticker = tickers.next().await.unwrap().unwrap();
+        assert_eq!("BTC/USD", ticker.symbol);
+        assert_eq!(Some(dec("5525.10000")), ticker.bid);
+        assert_eq!(Some(dec("5525.40000")), ticker.ask);
+        assert_eq!(Some(dec("5763.40000")), ticker.open);
+        assert_eq!(Some("online".to_string()), ws.status());
+
+        let mut trades = ws.watch_trades(&["BTC/USD"]).await.unwrap();
+        let first = trades.next().await.unwrap().unwrap();
+        let second = trades.next().await.unwrap().unwrap();
+        assert_eq!(dec("0.15850568"), first.amount);
+        assert_eq!(ccxt::exchange::Side::Sell, first.side);
+        assert_eq!(ccxt::exchange::Side::Buy, second.side);
+        assert_eq!("BTC/USD", second.symbol);
+    }
+
+    #[tokio::test]
+    async fn order_book_applies_updates() {
+        let (url, _requests) = serve(vec![
+            vec![SYSTEM_STATUS],
+            vec![
+                r#"{"channelID":10001,"channelName":"book-10","event":"subscriptionStatus","pair":"XBT/USD","status":"subscribed","subscription":{"depth":10,"name":"book"}}"#,
+                r#"[10001,{"as":[["5541.30000","2.50700000","1534614248.123678"],["5541.80000","0.33000000","1534614098.345543"]],"bs":[["5541.20000","1.52900000","1534614248.765567"],["5539.90000","0.30000000","1534614241.769870"]]},"book-10","XBT/USD"]"#,
+                r#"[10001,{"a":[["5541.30000","0.00000000","1534614335.345903"]]},{"b":[["5541.25000","0.40000000","1534614335.345903"]],"c":"7948111"},"book-10","XBT/USD"]"#,
+            ],
+        ]).await;
+        let ws = KrakenWs::connect_to(&url, kraken()).await.unwrap();
+        let mut books = ws.watch_order_book(&["BTC/USD"], 10).await.unwrap();
+
+        let snapshot = books.next().await.unwrap().unwrap();
+        assert_eq!(dec("5541.30000"), snapshot.asks[0].price);
+        assert_eq!(dec("5541.20000"), snapshot.bids[0].price);
+
+        let book = books.next().await.unwrap().unwrap();
+        assert_eq!(1, book.asks.len());
+        assert_eq!(dec("5541.80000"), book.asks[0].price);
+        assert_eq!(3, book.bids.len());
+        assert_eq!(dec("5541.25000"), book.bids[0].price);
+        assert_eq!(1534614335, book.timestamp.unwrap().timestamp());
+    }
+
+    #[tokio::test]
+    async fn order_book_resubscribes_on_checksum_mismatch() {
+        let (url, mut requests) = serve(vec![
+            vec![SYSTEM_STATUS],
+            vec![
+                r#"[10001,{"as":[["5541.30000","2.50700000","1534614248.123678"]],"bs":[["5541.20000","1.52900000","1534614248.765567"]]},"book-10","XBT/USD"]"#,
+                r#"[10001,{"a":[["5541.40000","1.00000000","1534614335.345903"]],"c":"12345"},"book-10","XBT/USD"]"#,
+            ],
+            vec![],
+            vec![r#"[10001,{"as":[["5542.00000","1.00000000","1534614400.000000"]],"bs":[["5541.00000","1.00000000","1534614400.000000"]]},"book-10","XBT/USD"]"#],
+        ]).await;
+        let ws = KrakenWs::connect_to(&url, kraken()).await.unwrap();
+        let mut books = ws.watch_order_book(&["BTC/USD"], 10).await.unwrap();
+        assert_eq!(dec("5541.30000"), books.next().await.unwrap().unwrap().asks[0].price);
+
+        requests.recv().await.unwrap();
+        let events = [requests.recv().await.unwrap(), requests.recv().await.unwrap()].iter()
+            .map(|r| serde_json::from_str::<serde_json::Value>(r).unwrap()["event"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["unsubscribe", "subscribe"], events);
+
+        let book = books.next().await.unwrap().unwrap();
+        assert_eq!(dec("5542.00000"), book.asks[0].price);
+        assert_eq!(1, book.asks.len());
+    }
+
+    #[tokio::test]
+    async fn ohlc_and_spread_streams() {
+        let (url, _requests) = serve(vec![
+            vec![SYSTEM_STATUS],
+            vec![r#"[42,["1542057314.748456","1542057600.000000","3586.70000","3586.70000","3586.60000","3586.60000","3586.68894","0.03373000",2],"ohlc-5","XBT/USD"]"#],
+            vec![r#"[43,["5698.40000","5700.00000","1542057299.545897","1.01234567","0.98765432"],"spread","XBT/EUR"]"#],
+        ]).await;
+        let ws = KrakenWs::connect_to(&url, kraken()).await.unwrap();
+
+        let mut candles = ws.watch_ohlcv(&["BTC/USD"], Timeframe::Minute5).await.unwrap();
+        let candle = candles.next().await.unwrap().unwrap();
+        assert_eq!(1542057300, candle.timestamp.timestamp());
+        assert_eq!(dec("0.03373000"), candle.volume);
+
+        let mut spreads = ws.watch_spread(&["BTC/EUR"]).await.unwrap();
+        let spread = spreads.next().await.unwrap().unwrap();
+        assert_eq!("BTC/EUR", spread.symbol);
+        assert_eq!(dec("5700.00000"), spread.ask);
+    }
+
+    #[tokio::test]
+    async fn subscription_errors_and_disconnects_end_streams() {
+        let (url, _requests) = serve(vec![
+            vec![SYSTEM_STATUS],
+            vec![r#"{"errorMessage":"Currency pair not supported","event":"subscriptionStatus","pair":"XBT/USDX","status":"error","subscription":{"name":"ticker"}}"#],
+            vec![r#"{"event":"heartbeat"}"#],
+        ]).await;
+        let ws = KrakenWs::connect_to(&url, kraken()).await.unwrap();
+
+        let mut tickers = ws.watch_ticker(&["BTC/USDX"]).await.unwrap();
+        assert!(matches!(tickers.next().await, Some(Err(Error::ApiCallError(_)))));
+        assert!(tickers.next().await.is_none());
+
+        let mut spreads = ws.watch_spread(&["BTC/USD"]).await.unwrap();
+        assert!(matches!(spreads.next().await, Some(Err(Error::ExchangeNotAvailable(_)))));
+        assert!(spreads.next().await.is_none());
+        assert!(matches!(ws.watch_spread(&["ETH/USD"]).await, Err(Error::ExchangeNotAvailable(_))));
+    }
+
+    #[tokio::test]
+    async fn lagging_streams_end_with_an_error() {
+        let trade = r#"["5541.20000","0.15850568","1534614057.321597","s","l",""]"#;
+        let trades = vec![trade; STREAM_CAPACITY + 100].join(",");
+        let message: &'static str = Box::leak(format!(r#"[337,[{}],"trade","XBT/USD"]"#, trades).into_boxed_str());
+        let (url, mut requests) = serve(vec![vec![SYSTEM_STATUS], vec![message], vec![]]).await;
+        let ws = KrakenWs::connect_to(&url, kraken()).await.unwrap();
+
+        let mut stream = ws.watch_trades(&["BTC/USD"]).await.unwrap();
+        requests.recv().await.unwrap();
+        let unsubscribe: serde_json::Value = serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+        assert_eq!("unsubscribe", unsubscribe["event"]);
+        let mut received = 0;
+        let last = loop {
+            match stream.next().await {
+                Some(Ok(_)) => received += 1,
+                other => break other,
+            }
+        };
+        assert!((STREAM_CAPACITY..STREAM_CAPACITY + 100).contains(&received), "{}", received);
+        assert!(matches!(last, Some(Err(Error::Lagged(_)))));
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,40 @@
+mod nonce_tests {
+    use ccxt::nonce::{AtomicNonce, FileNonce, NonceSource};
+    use std::sync::Arc;
+
+    #[test]
+    fn atomic_nonces_increase_across_threads() {
+        let source = Arc::new(AtomicNonce::new());
+        let threads: Vec<_> = (0..4).map(|_| {
+            let s = source.clone();
+            std::thread::spawn(move || (0..1000).map(|_| s.next().unwrap()).collect::<Vec<_>>())
+        }).collect();
+        let mut all: Vec<u64> = threads.into_iter().flat_map(|t| t.join().unwrap()).collect();
+        let count = all.len();
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), count);
+
+        let floor = source.next().unwrap() + 1_000_000;
+        source.resync(floor).unwrap();
+        assert!(source.next().unwrap() > floor);
+    }
+
+    #[test]
+    fn file_nonces_are_shared_and_persisted() {
+        let path = std::env::temp_dir().join(format!("ccxt-nonce-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let a = FileNonce::new(&path);
+        let b = FileNonce::new(&path);
+        let mut last = 0;
+        for _ in 0..100 {
+            for n in [a.next().unwrap(), b.next().unwrap()] {
+                assert!(n > last);
+                last = n;
+            }
+        }
+        b.resync(last + 1_000_000).unwrap();
+        assert!(FileNonce::new(&path).next().unwrap() > last + 1_000_000);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
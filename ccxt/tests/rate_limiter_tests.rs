@@ -0,0 +1,49 @@
+mod rate_limiter_tests {
+    use ccxt::kraken::Kraken;
+    use ccxt::rate_limiter::{DecayingCounter, RateLimiter};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_are_spaced_by_cost() {
+        let limiter = RateLimiter::new(Duration::from_millis(1000));
+        let start = Instant::now();
+        limiter.acquire(1).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        limiter.acquire(2).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(1000));
+        limiter.acquire(0).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(1000));
+        limiter.acquire(1).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(3000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn clones_share_the_schedule() {
+        let kraken = Kraken::new("kraken", String::new(), String::new());
+        let start = Instant::now();
+        let tasks: Vec<_> = (0..3).map(|_| {
+            let k = kraken.clone();
+            tokio::spawn(async move { k.rate_limiter.acquire(1).await })
+        }).collect();
+        for t in tasks {
+            t.await.unwrap();
+        }
+        assert_eq!(start.elapsed(), Duration::from_millis(6000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn counter_waits_for_decay() {
+        let counter = DecayingCounter::new(15, 0.5);
+        let start = Instant::now();
+        for _ in 0..15 {
+            counter.acquire(1).await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        counter.acquire(2).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(4));
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        counter.acquire(5).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(14));
+    }
+}
@@ -0,0 +1,30 @@
+mod registry_tests {
+    use ccxt::errors::Error;
+    use ccxt::exchange::{ApiConfig, Capability};
+    use ccxt::registry::{create_exchange, EXCHANGES};
+
+    #[tokio::test]
+    async fn create_exchange_by_id() {
+        for id in EXCHANGES {
+            let e = create_exchange(id, ApiConfig::default()).unwrap();
+            assert_eq!(e.exchange().id, id);
+        }
+        let coinbase = create_exchange("coinbase", ApiConfig::default()).unwrap();
+        assert!(matches!(coinbase.fetch_order_book("BTC/USD", None).await, Err(Error::ApiFunctionNotSupported(_))));
+        assert!(matches!(create_exchange("binance", ApiConfig::default()), Err(Error::UnknownExchange(_))));
+    }
+
+    #[test]
+    fn capabilities_follow_registered_functions() {
+        let kraken = create_exchange("kraken", ApiConfig::default()).unwrap().has();
+        assert_eq!(kraken["fetch_order_book"], Capability::Supported);
+        assert_eq!(kraken["fetch_tickers"], Capability::Supported);
+        assert_eq!(kraken["cancel_all_orders"], Capability::Emulated);
+
+        let coinbase = create_exchange("coinbase", ApiConfig::default()).unwrap().has();
+        assert_eq!(coinbase["fetch_ticker"], Capability::Supported);
+        assert_eq!(coinbase["fetch_tickers"], Capability::Emulated);
+        assert_eq!(coinbase["fetch_order_book"], Capability::Absent);
+        assert_eq!(coinbase["get_balance"], Capability::Supported);
+    }
+}
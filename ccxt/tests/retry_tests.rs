@@ -0,0 +1,93 @@
+mod common;
+
+mod retry_tests {
+    use crate::common::{coinbase, coinbase_advanced, kraken, serve};
+    use ccxt::exchange::ServerTime;
+    use ccxt::errors::Error;
+    use ccxt::exchange::Functionality;
+    use ccxt::retry::RetryPolicy;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_grows_with_jitter() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100), Duration::from_millis(1000));
+        for (attempt, max) in [(0, 100), (1, 200), (2, 400), (3, 800), (4, 1000), (40, 1000)] {
+            let d = policy.delay(attempt);
+            assert!(d >= Duration::from_millis(max / 2) && d <= Duration::from_millis(max), "{} {:?}", attempt, d);
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        let (url, requests) = serve(vec![
+            (503, "unavailable"),
+            (200, r#"{"error":["EService:Unavailable"],"result":null}"#),
+            (200, r#"{"error":[],"result":{"status":"online","timestamp":"2021-09-01T10:00:00Z"}}"#),
+        ]).await;
+        let status = kraken(url).get_data::<serde_json::Value>(&Functionality::SystemStatus, HashMap::new()).await.unwrap();
+        assert_eq!(status["status"], "online");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn add_order_is_retried_only_with_client_order_id() {
+        let (url, requests) = serve(vec![(200, r#"{"error":["EService:Busy"],"result":null}"#)]).await;
+        let k = kraken(url);
+        let r = k.get_data::<serde_json::Value>(&Functionality::AddOrder, HashMap::new()).await;
+        assert!(matches!(r, Err(Error::ExchangeNotAvailable(_))));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        let mut payload = HashMap::new();
+        payload.insert("cl_ord_id", "my-order-1".to_string());
+        let r = k.get_data::<serde_json::Value>(&Functionality::AddOrder, payload).await;
+        assert!(matches!(r, Err(Error::ExchangeNotAvailable(_))));
+        assert_eq!(requests.lock().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_not_retried() {
+        let (url, requests) = serve(vec![(200, r#"{"error":["EGeneral:Invalid arguments"],"result":null}"#)]).await;
+        let r = kraken(url).get_data::<serde_json::Value>(&Functionality::SystemStatus, HashMap::new()).await;
+        assert!(matches!(r, Err(Error::ApiCallError(_))));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        let (url, requests) = serve(vec![(200, r#"{"error":["EAPI:Rate limit exceeded"],"result":null}"#)]).await;
+        let r = kraken(url).get_data::<serde_json::Value>(&Functionality::SystemStatus, HashMap::new()).await;
+        assert!(matches!(r, Err(Error::RateLimitExceeded(_))));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_nonce_is_resent_after_resync() {
+        let (url, requests) = serve(vec![
+            (200, r#"{"error":["EAPI:Invalid nonce"],"result":null}"#),
+            (200, r#"{"error":[],"result":{"unixtime":1616336594,"rfc1123":"Sun, 21 Mar 21 14:23:14 +0000"}}"#),
+            (200, r#"{"error":[],"result":{"ZUSD":"1.0"}}"#),
+        ]).await;
+        let k = kraken(url).retry_policy(RetryPolicy::none());
+        let balance = k.get_data::<serde_json::Value>(&Functionality::Balance, HashMap::new()).await.unwrap();
+        assert_eq!(balance["ZUSD"], "1.0");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn coinbase_too_many_requests_is_retried() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1), Duration::from_millis(5));
+        let (url, requests) = serve(vec![
+            (429, r#"{"errors":[{"id":"rate_limit_exceeded","message":"Too many requests"}]}"#),
+            (200, r#"{"data":{"iso":"2021-09-01T10:00:00Z","epoch":1630490400}}"#),
+        ]).await;
+        let time = coinbase(url).retry_policy(policy).get_time().await.unwrap();
+        assert_eq!(1630490400, time.timestamp());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        let (url, requests) = serve(vec![
+            (429, r#"{"error":"RESOURCE_EXHAUSTED","message":"Too many requests"}"#),
+            (200, r#"{"iso":"2021-09-01T10:00:00Z","epochSeconds":"1630490400"}"#),
+        ]).await;
+        let time = coinbase_advanced(url).retry_policy(policy).get_time().await.unwrap();
+        assert_eq!(1630490400, time.timestamp());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+}